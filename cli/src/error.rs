@@ -5,6 +5,9 @@ pub enum ErrorKind {
     Library,
     MissingConfigFile,
     ConfigParseError,
+    InvalidFilter,
+    SignalHandler,
+    Report,
 }
 
 pub struct Error {
@@ -1,16 +1,74 @@
-use std::{io::Read, path::PathBuf, process::ExitCode};
+use std::{
+    io::Read,
+    path::PathBuf,
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        Arc,
+    },
+};
 
 use clap::Parser;
+use signal_hook::{
+    consts::{SIGINT, SIGTERM},
+    iterator::Signals,
+};
 
-use parallel_update::{types::*, update::Update, Updater};
-use parallel_update_config::config::{Config, UpdaterConfig};
+use parallel_update::{types::*, update::Update, History, Hooks, RunLogs, SudoKeepAlive, Updater};
+use parallel_update_config::{
+    config::{Config, UpdaterConfig},
+    types::shell_quote,
+};
 
 pub mod error;
-use error::Result;
+use error::{ErrorKind, Result};
+mod report;
+use report::{ReportFormat, Run};
+mod status;
+use status::StatusDisplay;
+
+/// Names of the updates with the given IDs
+fn names(ids: &[UpdateId], updates: &[Arc<Update>]) -> String {
+    ids.iter()
+        .map(|id| updates[id.0].name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Why an update ended up in a state (if there is more to say than the state's name)
+pub(crate) fn state_details(
+    update: &Update,
+    updates: &[Arc<Update>],
+    state: &State,
+) -> Option<String> {
+    Some(match state {
+        State::Failed(code) => code.to_string(),
+        State::Error(kind) => match update.error() {
+            Some(error) => error.chain(),
+            None => format!("{:?}", kind),
+        },
+        State::Panicked(message) => format!("panicked: {}", message),
+        State::TimedOut => "timed out".to_string(),
+        State::Killed(signal) => format!("killed by signal {}", signal),
+        State::Unschedulable(blocked) => match blocked {
+            Blocked::Dependency(id) => {
+                format!("dependency {} never finished", updates[id.0].name)
+            }
+            Blocked::Conflict(id) => {
+                format!("conflicting update {} never finished", updates[id.0].name)
+            }
+            Blocked::NoThreads => "no threads to run on".to_string(),
+        },
+        State::Skipped(SkipReason::MissingExecutable) => "executable not found".to_string(),
+        State::Skipped(SkipReason::Condition(condition)) => condition.clone(),
+        _ => return None,
+    })
+}
 
 fn print_update(
     update: &Update,
-    state: State,
+    updates: &[Arc<Update>],
+    state: &State,
     config: &UpdaterConfig,
     indent: usize,
     print_depends: bool,
@@ -19,18 +77,22 @@ fn print_update(
         eprint!("  ");
     }
 
-    eprint!("\x1b[1m{:?} {:?}", update.id, update.program);
+    eprint!(
+        "\x1b[1m{}\x1b[0m: {}\x1b[1m",
+        update.name,
+        update.program.command_line()
+    );
 
-    if let State::Failed(code) = state {
-        eprint!(" ({})", code);
+    if let Some(details) = state_details(update, updates, state) {
+        eprint!(" ({})", details);
     }
 
-    if let State::Error(error) = state {
-        eprint!(" ({:?})", error);
+    if update.attempts() > 1 {
+        eprint!(" after {} attempts", update.attempts());
     }
 
     if config.output_duration {
-        if let Some(ref output) = *update.output.lock().unwrap() {
+        if let Some(output) = update.output() {
             eprint!(" took {:?}", output.duration);
         } else {
             eprint!(" took ??");
@@ -40,14 +102,28 @@ fn print_update(
     eprintln!("\x1b[0m");
 
     if print_depends {
-        eprintln!("  Depends: {:?}", update.info.depends);
-        eprintln!("  Conflicts: {:?}", update.info.conflicts);
+        for _ in 0..=indent {
+            eprint!("  ");
+        }
+        eprintln!("Depends: {}", names(&update.info.depends, updates));
+        for _ in 0..=indent {
+            eprint!("  ");
+        }
+        eprintln!("Conflicts: {}", names(&update.info.conflicts, updates));
     }
 
-    if (config.output_success_logs && state == State::Success)
-        || (config.output_failure_logs && matches!(state, State::Failed(_) | State::Error(_)))
+    if (config.output_success_logs && *state == State::Success)
+        || (config.output_failure_logs
+            && matches!(
+                state,
+                State::Failed(_)
+                    | State::Error(_)
+                    | State::Panicked(_)
+                    | State::TimedOut
+                    | State::Killed(_)
+            ))
     {
-        if let Some(ref output) = *update.output.lock().unwrap() {
+        if let Some(output) = update.output() {
             let mut did_print = false;
 
             let stdout = std::str::from_utf8(&output.output.stdout).unwrap();
@@ -79,12 +155,118 @@ fn print_update(
     }
 }
 
+/// Print the updates that would be started together and how they would be run
+fn print_plan(updater: &Updater, config: &UpdaterConfig) {
+    let updates = updater.updates();
+    let plan = updater.plan(config.threads);
+
+    let current_dir = std::env::current_dir().ok();
+
+    for (i, wave) in plan.waves.iter().enumerate() {
+        eprintln!("\x1b[1mWave {}\x1b[0m:", i + 1);
+
+        for id in wave {
+            let update = &updates[id.0];
+            let program = &update.program;
+
+            eprintln!("  \x1b[1m{}\x1b[0m", update.name);
+            match update.expected_state() {
+                Some(State::Error(_)) => {
+                    eprintln!("    \x1b[31;1mFails\x1b[0m: executable not found")
+                }
+                Some(state) => {
+                    let details = state_details(update, updates, &state).unwrap_or_default();
+                    eprintln!("    \x1b[2;1mSkipped\x1b[0m: {}", details);
+                }
+                // Probes aren't run by a dry run
+                None => {
+                    if let Some(probe) = update.info.when.probe.as_ref() {
+                        eprintln!("    Would run probe: {}", probe);
+                    }
+                }
+            }
+            eprintln!("    Command: {}", program.command_line());
+
+            let mut environ = vec![if program.passthrough_environ {
+                "(inherited)".to_string()
+            } else {
+                "(cleared)".to_string()
+            }];
+            environ.extend(
+                program
+                    .environ
+                    .iter()
+                    .flatten()
+                    .map(|(key, value)| format!("{}={}", key, shell_quote(value))),
+            );
+            eprintln!("    Environment: {}", environ.join(" "));
+
+            let working_directory = program
+                .working_directory
+                .as_ref()
+                .map(PathBuf::from)
+                .or_else(|| current_dir.clone())
+                .unwrap_or_default();
+            eprintln!("    Directory: {}", working_directory.display());
+        }
+    }
+
+    if !plan.ignored.is_empty() {
+        eprintln!("\x1b[2;1mIgnored\x1b[0m (an update they depend on would fail):");
+        for id in &plan.ignored {
+            eprintln!("  \x1b[1m{}\x1b[0m", updates[id.0].name);
+        }
+    }
+
+    let planned: usize = plan.waves.iter().map(Vec::len).sum();
+    if planned + plan.ignored.len() < updates.len() {
+        eprintln!("\x1b[33;1mUnschedulable\x1b[0m:");
+        for update in updates {
+            if !plan.waves.iter().any(|wave| wave.contains(&update.id))
+                && !plan.ignored.contains(&update.id)
+            {
+                eprintln!("  \x1b[1m{}\x1b[0m", update.name);
+            }
+        }
+    }
+}
+
+/// Find where to store the durations of previous updates
+fn history_path(config: &UpdaterConfig) -> Option<PathBuf> {
+    if let Some(file) = config.history_file.as_ref() {
+        return Some(PathBuf::from(file));
+    }
+
+    let state_dir = if let Ok(state_home) = std::env::var("XDG_STATE_HOME") {
+        PathBuf::from(state_home)
+    } else {
+        PathBuf::from(std::env::var("HOME").ok()?).join(".local/state")
+    };
+
+    Some(state_dir.join(concat!(clap::crate_name!(), "/history")))
+}
+
 /// Run many update commands in parallel
 #[derive(Debug, Parser)]
 struct Args {
     /// The config file to use (defaults to "./updates.toml")
     #[arg(short, long)]
     config_file: Option<String>,
+    /// Only run these updates or groups (and their dependencies)
+    #[arg(long, value_delimiter = ',')]
+    only: Vec<String>,
+    /// Don't run these updates or groups
+    #[arg(long, value_delimiter = ',')]
+    skip: Vec<String>,
+    /// Print what would be run without running anything
+    #[arg(long)]
+    dry_run: bool,
+    /// Write a report of the run in this format
+    #[arg(long, value_enum)]
+    report: Option<ReportFormat>,
+    /// File to write the report to (defaults to stdout)
+    #[arg(long, requires = "report")]
+    report_file: Option<PathBuf>,
     #[command(flatten)]
     updater: Option<UpdaterConfig>,
 }
@@ -124,7 +306,26 @@ fn main() -> Result<ExitCode> {
 
     config.read_to_string(&mut config_str)?;
 
-    let config: Config = toml::from_str(&config_str)?;
+    let mut config: Config = toml::from_str(&config_str)?;
+
+    let problems = config.filter(&args.only, &args.skip);
+    if !problems.unmatched.is_empty() {
+        return Err(ErrorKind::InvalidFilter.context(format!(
+            "No updates or groups named: {}",
+            problems.unmatched.join(", ")
+        )));
+    }
+    if !problems.skipped_dependencies.is_empty() {
+        return Err(ErrorKind::InvalidFilter.context(format!(
+            "Selected updates depend on skipped updates: {}",
+            problems
+                .skipped_dependencies
+                .iter()
+                .map(|(name, dependency)| format!("{} depends on {}", name, dependency))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )));
+    }
 
     let (mut c, updater) = Updater::try_from_config(config)?;
 
@@ -138,18 +339,118 @@ fn main() -> Result<ExitCode> {
         eprintln!("{:#?}", c);
 
         for update in updater.updates() {
-            print_update(update, State::Pending, &c, 0, true);
+            print_update(update, updater.updates(), &State::Pending, &c, 0, true);
         }
 
         return Ok(ExitCode::SUCCESS);
     }
 
+    let history_path = history_path(&c);
+    let mut history = match history_path.as_ref().map(History::load) {
+        Some(Ok(history)) => history,
+        Some(Err(e)) => {
+            eprintln!("Unable to load update history: {}", e);
+            History::new()
+        }
+        None => History::new(),
+    };
+
+    let updater = updater.history(&history).hooks(Hooks::from_config(&c));
+
+    if args.dry_run {
+        print_plan(&updater, &c);
+
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let updater = match c.log_dir.as_ref() {
+        Some(log_dir) => match RunLogs::create(log_dir) {
+            Ok(logs) => {
+                if c.log_retention > 0 {
+                    if let Err(e) = logs.prune(c.log_retention) {
+                        eprintln!("Unable to remove old update logs: {}", e);
+                    }
+                }
+
+                updater.logs(&logs)
+            }
+            Err(e) => {
+                eprintln!("Unable to create update log directory: {}", e);
+                updater
+            }
+        },
+        None => updater,
+    };
+
+    // Ask for the sudo password once rather than for each update
+    let _sudo = if c.sudo_keep_alive && updater.updates().iter().any(|update| update.info.root) {
+        match SudoKeepAlive::start() {
+            Ok(sudo) => Some(sudo),
+            Err(e) => {
+                eprintln!("Unable to keep sudo credentials alive: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // Stop starting updates on Ctrl-C and let running updates handle the signal
+    let cancel = updater.cancel_handle();
+    // The first signal received, used for the exit code
+    let cancelled_by = Arc::new(AtomicI32::new(0));
+    let mut signals =
+        Signals::new([SIGINT, SIGTERM]).map_err(|e| ErrorKind::SignalHandler.context(e))?;
+    {
+        let cancelled_by = Arc::clone(&cancelled_by);
+        std::thread::spawn(move || {
+            for signal in signals.forever() {
+                let _ =
+                    cancelled_by.compare_exchange(0, signal, Ordering::SeqCst, Ordering::SeqCst);
+                cancel.cancel(signal);
+            }
+        });
+    }
+
+    let status = c
+        .live_status
+        .then(|| StatusDisplay::start(updater.updates(), updater.subscribe()));
+
+    let started = std::time::SystemTime::now();
     let start = std::time::Instant::now();
     let results = updater.run(c.threads);
     let duration = start.elapsed();
 
-    let results: Vec<_> = results
-        .into_iter()
+    drop(status);
+
+    if let Some(history_path) = history_path.as_ref() {
+        history.record_updates(&results);
+        if let Err(e) = history.save(history_path) {
+            eprintln!("Unable to save update history: {}", e);
+        }
+    }
+
+    if let Some(format) = args.report {
+        let run = Run {
+            updates: &results,
+            started,
+            duration,
+        };
+
+        let written = match args.report_file.as_ref() {
+            Some(file) => std::fs::File::create(file)
+                .map_err(|e| ErrorKind::Report.context(e))
+                .and_then(|file| report::write_report(format, &run, std::io::BufWriter::new(file))),
+            None => report::write_report(format, &run, std::io::stdout().lock()),
+        };
+        if let Err(e) = written {
+            eprintln!("Unable to write report: {}", e);
+        }
+    }
+
+    let updates = results;
+    let results: Vec<_> = updates
+        .iter()
         .map(|update| (update.state.get(), update))
         .collect();
 
@@ -159,18 +460,39 @@ fn main() -> Result<ExitCode> {
         .collect();
     let failed: Vec<_> = results
         .iter()
-        .filter(|(state, _)| matches!(state, State::Failed(_)))
+        .filter(|(state, _)| {
+            matches!(
+                state,
+                State::Failed(_)
+                    | State::Error(_)
+                    | State::Panicked(_)
+                    | State::TimedOut
+                    | State::Killed(_)
+            )
+        })
         .collect();
     let ignored: Vec<_> = results
         .iter()
         .filter(|(state, _)| *state == State::Ignored)
         .collect();
+    let cancelled: Vec<_> = results
+        .iter()
+        .filter(|(state, _)| *state == State::Cancelled)
+        .collect();
+    let skipped: Vec<_> = results
+        .iter()
+        .filter(|(state, _)| matches!(state, State::Skipped(_)))
+        .collect();
+    let unschedulable: Vec<_> = results
+        .iter()
+        .filter(|(state, _)| matches!(state, State::Unschedulable(_)))
+        .collect();
 
     if c.output_states {
         if !successful.is_empty() {
             eprintln!("\x1b[32;1mSuccess\x1b[0m:");
             for (state, update) in &successful {
-                print_update(update, *state, &c, 1, false);
+                print_update(update, &updates, state, &c, 1, false);
             }
             eprintln!();
         }
@@ -178,7 +500,7 @@ fn main() -> Result<ExitCode> {
         if !failed.is_empty() {
             eprintln!("\x1b[31;1mFailed\x1b[0m:");
             for (state, update) in &failed {
-                print_update(update, *state, &c, 1, false);
+                print_update(update, &updates, state, &c, 1, false);
             }
             eprintln!();
         }
@@ -186,7 +508,31 @@ fn main() -> Result<ExitCode> {
         if !ignored.is_empty() {
             eprintln!("\x1b[2;1mIgnored\x1b[0m:");
             for (state, update) in &ignored {
-                print_update(update, *state, &c, 1, true);
+                print_update(update, &updates, state, &c, 1, true);
+            }
+            eprintln!();
+        }
+
+        if !cancelled.is_empty() {
+            eprintln!("\x1b[2;1mCancelled\x1b[0m:");
+            for (state, update) in &cancelled {
+                print_update(update, &updates, state, &c, 1, false);
+            }
+            eprintln!();
+        }
+
+        if !skipped.is_empty() {
+            eprintln!("\x1b[2;1mSkipped\x1b[0m:");
+            for (state, update) in &skipped {
+                print_update(update, &updates, state, &c, 1, false);
+            }
+            eprintln!();
+        }
+
+        if !unschedulable.is_empty() {
+            eprintln!("\x1b[33;1mUnschedulable\x1b[0m:");
+            for (state, update) in &unschedulable {
+                print_update(update, &updates, state, &c, 1, true);
             }
             eprintln!();
         }
@@ -196,7 +542,15 @@ fn main() -> Result<ExitCode> {
         eprintln!("Total time: {:?}", duration);
     }
 
-    Ok(if failed.is_empty() && ignored.is_empty() {
+    // Exit like a process killed by the signal that cancelled the run
+    let signal = cancelled_by.load(Ordering::SeqCst);
+    Ok(if signal != 0 {
+        ExitCode::from((128 + signal) as u8)
+    } else if failed.is_empty()
+        && ignored.is_empty()
+        && cancelled.is_empty()
+        && unschedulable.is_empty()
+    {
         ExitCode::SUCCESS
     } else {
         ExitCode::FAILURE
@@ -0,0 +1,369 @@
+use std::{
+    io::Write,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use serde::Serialize;
+
+use parallel_update::{time::rfc3339, types::*, update::Update};
+
+use crate::error::{ErrorKind, Result};
+
+/// Version of the JSON report, incremented whenever a field is changed or removed
+pub const REPORT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    Json,
+    Junit,
+}
+
+/// The results of a whole run
+#[derive(Debug)]
+pub struct Run<'a> {
+    pub updates: &'a [Arc<Update>],
+    pub started: SystemTime,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonReport<'a> {
+    version: u32,
+    started: String,
+    finished: String,
+    duration_secs: f64,
+    totals: Totals,
+    updates: Vec<JsonUpdate<'a>>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Totals {
+    total: usize,
+    success: usize,
+    failed: usize,
+    error: usize,
+    panicked: usize,
+    timed_out: usize,
+    killed: usize,
+    ignored: usize,
+    unschedulable: usize,
+    cancelled: usize,
+    skipped: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonUpdate<'a> {
+    name: &'a str,
+    state: &'static str,
+    /// Exit code of a failed update
+    exit_code: Option<i32>,
+    /// Signal that killed the update
+    signal: Option<i32>,
+    /// Kind of error that stopped the update from running
+    error: Option<String>,
+    /// What caused the error, e.g. "Unable to start "paru": No such file or directory (os error 2)"
+    error_message: Option<String>,
+    /// Message of the panic that stopped the update
+    panic_message: Option<String>,
+    /// Why an unschedulable update couldn't be started
+    blocked: Option<JsonBlocked<'a>>,
+    /// Why a skipped update wasn't run
+    skipped: Option<JsonSkipped>,
+    attempts: usize,
+    /// Timing of the last attempt
+    started: Option<String>,
+    finished: Option<String>,
+    duration_secs: Option<f64>,
+    /// Output of the last attempt
+    stdout: Option<String>,
+    stderr: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonBlocked<'a> {
+    reason: &'static str,
+    /// The update that was waited on
+    update: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonSkipped {
+    reason: &'static str,
+    /// The condition that wasn't met
+    condition: Option<String>,
+}
+
+impl Totals {
+    fn count(&mut self, state: State) {
+        self.total += 1;
+        match state {
+            State::Success => self.success += 1,
+            State::Failed(_) => self.failed += 1,
+            State::Error(_) => self.error += 1,
+            State::Panicked(_) => self.panicked += 1,
+            State::TimedOut => self.timed_out += 1,
+            State::Killed(_) => self.killed += 1,
+            State::Ignored => self.ignored += 1,
+            State::Unschedulable(_) => self.unschedulable += 1,
+            State::Cancelled => self.cancelled += 1,
+            State::Skipped(_) => self.skipped += 1,
+            State::Pending | State::Starting | State::Running => {}
+        }
+    }
+}
+
+impl<'a> JsonUpdate<'a> {
+    fn new(update: &'a Update, updates: &'a [Arc<Update>]) -> JsonUpdate<'a> {
+        let state = update.state.get();
+        let output = update.output();
+
+        JsonUpdate {
+            name: &update.name,
+            state: state.name(),
+            exit_code: match state {
+                State::Failed(code) => Some(code),
+                _ => None,
+            },
+            signal: match state {
+                State::Killed(signal) => Some(signal),
+                _ => None,
+            },
+            error: match state {
+                State::Error(kind) => Some(format!("{:?}", kind)),
+                _ => None,
+            },
+            error_message: update.error().map(|error| error.chain()),
+            panic_message: match &state {
+                State::Panicked(message) => Some(message.clone()),
+                _ => None,
+            },
+            blocked: match state {
+                State::Unschedulable(blocked) => Some(match blocked {
+                    Blocked::Dependency(id) => JsonBlocked {
+                        reason: "dependency",
+                        update: Some(&updates[id.0].name),
+                    },
+                    Blocked::Conflict(id) => JsonBlocked {
+                        reason: "conflict",
+                        update: Some(&updates[id.0].name),
+                    },
+                    Blocked::NoThreads => JsonBlocked {
+                        reason: "no_threads",
+                        update: None,
+                    },
+                }),
+                _ => None,
+            },
+            skipped: match state {
+                State::Skipped(SkipReason::MissingExecutable) => Some(JsonSkipped {
+                    reason: "missing_executable",
+                    condition: None,
+                }),
+                State::Skipped(SkipReason::Condition(ref condition)) => Some(JsonSkipped {
+                    reason: "condition",
+                    condition: Some(condition.clone()),
+                }),
+                _ => None,
+            },
+            attempts: update.attempts(),
+            started: output.as_ref().map(|output| rfc3339(output.started)),
+            finished: output
+                .as_ref()
+                .map(|output| rfc3339(output.started + output.duration)),
+            duration_secs: output.as_ref().map(|output| output.duration.as_secs_f64()),
+            stdout: output
+                .as_ref()
+                .map(|output| String::from_utf8_lossy(&output.output.stdout).into_owned()),
+            stderr: output
+                .as_ref()
+                .map(|output| String::from_utf8_lossy(&output.output.stderr).into_owned()),
+        }
+    }
+}
+
+fn write_json(run: &Run, mut writer: impl Write) -> Result<()> {
+    let mut totals = Totals::default();
+    for update in run.updates {
+        totals.count(update.state.get());
+    }
+
+    let report = JsonReport {
+        version: REPORT_VERSION,
+        started: rfc3339(run.started),
+        finished: rfc3339(run.started + run.duration),
+        duration_secs: run.duration.as_secs_f64(),
+        totals,
+        updates: run
+            .updates
+            .iter()
+            .map(|update| JsonUpdate::new(update, run.updates))
+            .collect(),
+    };
+
+    serde_json::to_writer_pretty(&mut writer, &report).map_err(|e| ErrorKind::Report.context(e))?;
+    writeln!(writer)
+        .and_then(|_| writer.flush())
+        .map_err(|e| ErrorKind::Report.context(e))?;
+
+    Ok(())
+}
+
+/// Escape text for use in XML, removing characters XML can't contain (e.g. terminal escape codes)
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Write a JUnit XML report with a test case for each update
+fn write_junit(run: &Run, mut writer: impl Write) -> std::io::Result<()> {
+    let mut totals = Totals::default();
+    for update in run.updates {
+        totals.count(update.state.get());
+    }
+    let failures = totals.failed
+        + totals.error
+        + totals.panicked
+        + totals.timed_out
+        + totals.killed
+        + totals.unschedulable;
+    let skipped = totals.ignored + totals.cancelled + totals.skipped;
+
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        writer,
+        r#"<testsuites name="{name}" tests="{}" failures="{}" errors="0" skipped="{}" time="{:.3}">"#,
+        totals.total,
+        failures,
+        skipped,
+        run.duration.as_secs_f64(),
+        name = clap::crate_name!(),
+    )?;
+    writeln!(
+        writer,
+        r#"  <testsuite name="{name}" tests="{}" failures="{}" errors="0" skipped="{}" time="{:.3}" timestamp="{}">"#,
+        totals.total,
+        failures,
+        skipped,
+        run.duration.as_secs_f64(),
+        rfc3339(run.started),
+        name = clap::crate_name!(),
+    )?;
+
+    for update in run.updates {
+        let state = update.state.get();
+        let output = update.output();
+
+        writeln!(
+            writer,
+            r#"    <testcase name="{}" classname="{}" time="{:.3}">"#,
+            xml_escape(&update.name),
+            clap::crate_name!(),
+            output
+                .as_ref()
+                .map(|output| output.duration.as_secs_f64())
+                .unwrap_or_default(),
+        )?;
+
+        let failure = match state {
+            State::Failed(code) => Some(("failed", format!("Exited with code {}", code))),
+            State::Error(kind) => Some((
+                "error",
+                match update.error() {
+                    Some(error) => error.chain(),
+                    None => format!("Unable to run: {:?}", kind),
+                },
+            )),
+            State::Panicked(ref message) => Some(("panicked", format!("Panicked: {}", message))),
+            State::TimedOut => Some(("timed_out", "Timed out".to_string())),
+            State::Killed(signal) => Some(("killed", format!("Killed by signal {}", signal))),
+            State::Unschedulable(blocked) => Some((
+                "unschedulable",
+                match blocked {
+                    Blocked::Dependency(id) => {
+                        format!("Dependency {} never finished", run.updates[id.0].name)
+                    }
+                    Blocked::Conflict(id) => format!(
+                        "Conflicting update {} never finished",
+                        run.updates[id.0].name
+                    ),
+                    Blocked::NoThreads => "No threads to run on".to_string(),
+                },
+            )),
+            _ => None,
+        };
+        if let Some((kind, message)) = failure {
+            writeln!(
+                writer,
+                r#"      <failure type="{}" message="{}"/>"#,
+                kind,
+                xml_escape(&message)
+            )?;
+        }
+
+        let skipped = match state {
+            State::Ignored => Some("A dependency failed".to_string()),
+            State::Cancelled => Some("The run was cancelled".to_string()),
+            State::Skipped(SkipReason::MissingExecutable) => {
+                Some("The executable wasn't found".to_string())
+            }
+            State::Skipped(SkipReason::Condition(ref condition)) => {
+                Some(format!("Condition not met: {}", condition))
+            }
+            _ => None,
+        };
+        if let Some(message) = skipped {
+            writeln!(
+                writer,
+                r#"      <skipped message="{}"/>"#,
+                xml_escape(&message)
+            )?;
+        }
+
+        if let Some(output) = output.as_ref() {
+            let stdout = String::from_utf8_lossy(&output.output.stdout);
+            if !stdout.is_empty() {
+                writeln!(
+                    writer,
+                    "      <system-out>{}</system-out>",
+                    xml_escape(&stdout)
+                )?;
+            }
+            let stderr = String::from_utf8_lossy(&output.output.stderr);
+            if !stderr.is_empty() {
+                writeln!(
+                    writer,
+                    "      <system-err>{}</system-err>",
+                    xml_escape(&stderr)
+                )?;
+            }
+        }
+
+        writeln!(writer, "    </testcase>")?;
+    }
+
+    writeln!(writer, "  </testsuite>")?;
+    writeln!(writer, "</testsuites>")?;
+
+    writer.flush()
+}
+
+/// Write a report of the run in the given format
+pub fn write_report(format: ReportFormat, run: &Run, writer: impl Write) -> Result<()> {
+    match format {
+        ReportFormat::Json => write_json(run, writer),
+        ReportFormat::Junit => write_junit(run, writer).map_err(|e| ErrorKind::Report.context(e)),
+    }
+}
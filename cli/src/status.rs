@@ -0,0 +1,253 @@
+use std::{
+    fmt::Write as _,
+    io::{IsTerminal, Write},
+    sync::{
+        mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, SystemTime},
+};
+
+use parallel_update::{
+    events::{Event, EventKind},
+    types::*,
+    update::Update,
+};
+
+/// How often the status panel is redrawn
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Shows the progress of each update while the updater runs.
+///
+/// On a terminal a panel is redrawn in place, it stops being redrawn while an update is using the
+/// terminal for input and starts again below that update's output once input is released.
+/// Otherwise a line is logged each time an update changes state.
+#[derive(Debug)]
+pub struct StatusDisplay {
+    stop: Sender<()>,
+    handle: Option<JoinHandle<()>>,
+}
+
+/// What is known about an update since the display started
+struct Row {
+    update: Arc<Update>,
+    state: State,
+    started: Option<SystemTime>,
+    last_line: String,
+}
+
+impl Row {
+    fn new(update: Arc<Update>) -> Row {
+        Row {
+            state: update.state.get(),
+            started: None,
+            last_line: String::new(),
+            update,
+        }
+    }
+
+    fn elapsed(&self) -> Option<Duration> {
+        if self.state.is_running() {
+            self.started.and_then(|started| started.elapsed().ok())
+        } else if self.state.is_done() {
+            self.update.output().map(|output| output.duration)
+        } else {
+            None
+        }
+    }
+}
+
+impl StatusDisplay {
+    /// Start showing progress from the updater's events
+    pub fn start(updates: &[Arc<Update>], events: Receiver<Event>) -> StatusDisplay {
+        let updates = updates.to_vec();
+        let mut rows: Vec<_> = updates.iter().cloned().map(Row::new).collect();
+
+        let (stop, stopped) = channel();
+        let handle = std::thread::spawn(move || {
+            let tty = std::io::stderr().is_terminal();
+            // Number of lines of the panel that are currently on screen
+            let mut drawn = 0;
+            let mut input_held = false;
+
+            loop {
+                let stopping =
+                    stopped.recv_timeout(REDRAW_INTERVAL) != Err(RecvTimeoutError::Timeout);
+
+                let mut changed = Vec::new();
+                for event in events.try_iter() {
+                    match event.kind {
+                        EventKind::StateChanged { id, from, to } => {
+                            let row = &mut rows[id.0];
+                            if to.is_running() && !from.is_running() {
+                                row.started = Some(event.time);
+                            }
+                            // How long the update ran for when this change ended the run, later
+                            // changes in the same batch may already have started it again
+                            let ran_for = match row.started {
+                                Some(started) if from.is_running() && to.is_done() => {
+                                    event.time.duration_since(started).ok()
+                                }
+                                _ => None,
+                            };
+                            row.state = to.clone();
+                            changed.push((id, to, ran_for));
+                        }
+                        EventKind::InputAcquired { .. } => input_held = true,
+                        EventKind::InputReleased { .. } => input_held = false,
+                        EventKind::Output { id, line } => {
+                            rows[id.0].last_line = plain_text(&line.text)
+                        }
+                        EventKind::UpdateFinished { .. } | EventKind::RunFinished => {}
+                    }
+                }
+
+                let mut stderr = std::io::stderr().lock();
+                if !tty {
+                    for (id, state, ran_for) in changed
+                        .into_iter()
+                        .filter(|(_, state, _)| *state != State::Starting)
+                    {
+                        let line = log_line(&rows[id.0], &updates, state, ran_for);
+                        let _ = writeln!(stderr, "{}", line);
+                    }
+                } else if stopping {
+                    let _ = write!(stderr, "{}", clear(drawn));
+                } else if input_held {
+                    // Leave the panel where it is, the update's output may already follow it
+                    drawn = 0;
+                } else {
+                    let mut panel = clear(drawn);
+                    drawn = draw(&mut panel, &rows, &updates);
+                    let _ = write!(stderr, "{}", panel);
+                }
+                let _ = stderr.flush();
+
+                if stopping {
+                    break;
+                }
+            }
+        });
+
+        StatusDisplay {
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for StatusDisplay {
+    fn drop(&mut self) {
+        let _ = self.stop.send(());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Escape codes that erase the last `lines` lines
+fn clear(lines: usize) -> String {
+    if lines == 0 {
+        String::new()
+    } else {
+        format!("\x1b[{}A\r\x1b[J", lines)
+    }
+}
+
+/// Add a line per update (as many as fit on the terminal) to the panel, returning the number of
+/// lines added
+fn draw(panel: &mut String, rows: &[Row], updates: &[Arc<Update>]) -> usize {
+    let (width, height) = terminal_size();
+    let name_width = rows
+        .iter()
+        .map(|row| row.update.name.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    // Leave a line for the cursor and one to say how many updates are hidden
+    let shown = if rows.len() < height {
+        rows.len()
+    } else {
+        height.saturating_sub(2)
+    };
+
+    for row in &rows[..shown] {
+        let elapsed = row
+            .elapsed()
+            .map(|elapsed| format!("{:.1}s", elapsed.as_secs_f64()))
+            .unwrap_or_default();
+
+        let line = format!(
+            "{:name_width$} {:16} {:>7} {}",
+            row.update.name,
+            describe(row, updates, &row.state),
+            elapsed,
+            row.last_line
+        );
+        let line: String = line.chars().take(width.saturating_sub(1)).collect();
+
+        let _ = writeln!(panel, "{}", line);
+    }
+
+    if shown < rows.len() {
+        let _ = writeln!(panel, "... {} more", rows.len() - shown);
+        shown + 1
+    } else {
+        shown
+    }
+}
+
+/// The state's name with why the update ended up in it
+fn describe(row: &Row, updates: &[Arc<Update>], state: &State) -> String {
+    match crate::state_details(&row.update, updates, state) {
+        Some(details) => format!("{} ({})", state.name(), details),
+        None => state.name().to_string(),
+    }
+}
+
+/// A line logged when an update changes state, with how long it ran for if the change ended it
+fn log_line(row: &Row, updates: &[Arc<Update>], state: State, ran_for: Option<Duration>) -> String {
+    let mut line = format!("{}: {}", row.update.name, describe(row, updates, &state));
+    if let Some(ran_for) = ran_for {
+        let _ = write!(line, " after {:.1}s", ran_for.as_secs_f64());
+    }
+
+    line
+}
+
+/// Remove anything from a line of output that would move the cursor (escape codes, carriage
+/// returns used to redraw progress bars, ...)
+fn plain_text(line: &str) -> String {
+    let line = line.rsplit('\r').next().unwrap_or_default();
+
+    let mut text = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip the escape sequence up to its final letter
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else if !c.is_control() {
+            text.push(c);
+        }
+    }
+
+    text.trim().to_string()
+}
+
+/// The (width, height) of the terminal in characters
+fn terminal_size() -> (usize, usize) {
+    // SAFETY: size is only accessed through a valid pointer
+    unsafe {
+        let mut size: libc::winsize = std::mem::zeroed();
+        if libc::ioctl(libc::STDERR_FILENO, libc::TIOCGWINSZ, &mut size) == 0 && size.ws_col > 0 {
+            (size.ws_col as usize, size.ws_row as usize)
+        } else {
+            (80, 24)
+        }
+    }
+}
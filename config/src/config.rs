@@ -1,9 +1,9 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::{
-    primatives::UpdateKind,
-    types::Program,
-    util::{default_true, default_two},
+    primatives::{IfMissing, Prompt, UpdateKind},
+    types::{Program, When},
+    util::{default_ten, default_true, default_two},
 };
 
 /// Config for a specfic updater
@@ -16,15 +16,49 @@ pub struct UpdateConfig {
     /// Whether the update requires exclusive input
     #[cfg_attr(feature = "serde", serde(default))]
     pub input: bool,
-    /// Whether the update uses a program that gives root (e.g. sudo)
+    /// Whether the update uses a program that gives root (e.g. sudo), sudo credentials are kept
+    /// alive during the run when any update does
     #[cfg_attr(feature = "serde", serde(default))]
     pub root: bool,
+    /// Prompts that input is forwarded for in order (for interactive updates), input is released
+    /// after the last prompt
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub prompts: Vec<Prompt>,
+    /// Run the update in a pseudo-terminal, keeping colors and progress bars while capturing its
+    /// output
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub pty: bool,
     /// Updates that cannot run at the same time (order doesn't matter)
     #[cfg_attr(feature = "serde", serde(default))]
     pub conflicts: Vec<String>,
     /// Updates that must run before
     #[cfg_attr(feature = "serde", serde(default))]
     pub depends: Vec<String>,
+    /// Groups the update belongs to (used to select updates on the command line)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub groups: Vec<String>,
+    /// Scheduling priority, higher priority updates are started first
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub priority: i32,
+    /// Seconds the update may run for before it (and everything it started) is killed, updates
+    /// that use input need `pty` for a timeout
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub timeout: Option<u64>,
+    /// How many times to retry the update if it fails
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub retries: usize,
+    /// Seconds to wait before retrying a failed update
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub retry_delay: u64,
+    /// Double the retry delay after each failed attempt
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub retry_backoff: bool,
+    /// What to do when the executable isn't found (in PATH when it isn't a path)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub if_missing: IfMissing,
+    /// Conditions that must be met for the update to run, otherwise it is skipped
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub when: When,
     #[cfg_attr(feature = "serde", serde(flatten))]
     pub program: Program,
 }
@@ -50,6 +84,10 @@ pub struct UpdaterConfig {
     #[cfg_attr(feature = "serde", serde(default = "default_true"))]
     #[cfg_attr(feature = "clap", arg(long, default_value = "true"))]
     pub output_states: bool,
+    /// Show the progress of each update while updating
+    #[cfg_attr(feature = "serde", serde(default = "default_true"))]
+    #[cfg_attr(feature = "clap", arg(long, default_value = "true"))]
+    pub live_status: bool,
     /// Number of updates to run at once
     #[cfg_attr(feature = "serde", serde(default = "default_two"))]
     #[cfg_attr(feature = "clap", arg(short, long, default_value = "2"))]
@@ -58,6 +96,37 @@ pub struct UpdaterConfig {
     #[cfg_attr(feature = "serde", serde(default))]
     #[cfg_attr(feature = "clap", arg(long))]
     pub debug_config: bool,
+    /// File to store update durations in, used to start long dependency chains first (defaults to
+    /// "~/.local/state/parallel-update-cli/history")
+    #[cfg_attr(feature = "serde", serde(default))]
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub history_file: Option<String>,
+    /// Ask for the sudo password once at startup and keep it cached while updating (only when
+    /// an update uses root)
+    #[cfg_attr(feature = "serde", serde(default = "default_true"))]
+    #[cfg_attr(feature = "clap", arg(long, default_value = "true"))]
+    pub sudo_keep_alive: bool,
+    /// Directory to save the output of each update in, each run gets its own timestamped
+    /// directory
+    #[cfg_attr(feature = "serde", serde(default))]
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub log_dir: Option<String>,
+    /// Number of runs to keep logs for (0 keeps all of them)
+    #[cfg_attr(feature = "serde", serde(default = "default_ten"))]
+    #[cfg_attr(feature = "clap", arg(long, default_value = "10"))]
+    pub log_retention: usize,
+    /// Shell command run when an update needs input
+    #[cfg_attr(feature = "serde", serde(default))]
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub on_input_needed: Option<String>,
+    /// Shell command run when each update is done
+    #[cfg_attr(feature = "serde", serde(default))]
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub on_update_done: Option<String>,
+    /// Shell command run when all updates are done
+    #[cfg_attr(feature = "serde", serde(default))]
+    #[cfg_attr(feature = "clap", arg(long))]
+    pub on_run_done: Option<String>,
 }
 
 macro_rules! override_if_not_default {
@@ -66,6 +135,11 @@ macro_rules! override_if_not_default {
             $self.$member = $other.$member;
         }
     };
+    ($self: ident, $other: ident, $default: ident, $member: ident, clone) => {
+        if $other.$member != $default.$member {
+            $self.$member = $other.$member.clone();
+        }
+    };
 }
 
 impl UpdaterConfig {
@@ -77,9 +151,17 @@ impl UpdaterConfig {
         override_if_not_default!(self, other, default, output_success_logs);
         override_if_not_default!(self, other, default, output_failure_logs);
         override_if_not_default!(self, other, default, output_states);
+        override_if_not_default!(self, other, default, live_status);
         // FIXME: This doesn't work well for numbers
         override_if_not_default!(self, other, default, threads);
         override_if_not_default!(self, other, default, debug_config);
+        override_if_not_default!(self, other, default, history_file, clone);
+        override_if_not_default!(self, other, default, sudo_keep_alive);
+        override_if_not_default!(self, other, default, log_dir, clone);
+        override_if_not_default!(self, other, default, log_retention);
+        override_if_not_default!(self, other, default, on_input_needed, clone);
+        override_if_not_default!(self, other, default, on_update_done, clone);
+        override_if_not_default!(self, other, default, on_run_done, clone);
     }
 
     pub const fn default() -> UpdaterConfig {
@@ -88,8 +170,16 @@ impl UpdaterConfig {
             output_success_logs: false,
             output_failure_logs: true,
             output_states: true,
+            live_status: true,
             threads: 2,
             debug_config: false,
+            history_file: None,
+            sudo_keep_alive: true,
+            log_dir: None,
+            log_retention: 10,
+            on_input_needed: None,
+            on_update_done: None,
+            on_run_done: None,
         }
     }
 }
@@ -107,3 +197,195 @@ pub struct Config {
     /// Update configuration
     pub updates: HashMap<String, UpdateConfig>,
 }
+
+impl UpdateConfig {
+    /// Whether the update has the name or is in the group
+    pub fn matches(&self, name: &str, name_or_group: &str) -> bool {
+        name == name_or_group || self.groups.iter().any(|group| group == name_or_group)
+    }
+}
+
+/// Problems with a filter applied by [Config::filter]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FilterProblems {
+    /// Names/groups that didn't match any update
+    pub unmatched: Vec<String>,
+    /// Selected updates and the skipped updates they depend on
+    pub skipped_dependencies: Vec<(String, String)>,
+}
+
+impl FilterProblems {
+    pub fn is_empty(&self) -> bool {
+        self.unmatched.is_empty() && self.skipped_dependencies.is_empty()
+    }
+}
+
+impl Config {
+    /// Remove updates not selected by `only` (all updates if empty) or selected by `skip`, each
+    /// being an update name or group. Dependencies of selected updates are kept, references to
+    /// removed updates are dropped.
+    ///
+    /// Skipping a dependency of a selected update would let the update run without it, so the
+    /// config shouldn't be used when any problems are returned.
+    pub fn filter(&mut self, only: &[String], skip: &[String]) -> FilterProblems {
+        let unmatched = only
+            .iter()
+            .chain(skip)
+            .filter(|pattern| {
+                !self
+                    .updates
+                    .iter()
+                    .any(|(name, update)| update.matches(name, pattern))
+            })
+            .cloned()
+            .collect();
+
+        let is_skipped = |name: &str, update: &UpdateConfig| {
+            skip.iter().any(|pattern| update.matches(name, pattern))
+        };
+
+        let mut selected: Vec<&String> = self
+            .updates
+            .iter()
+            .filter(|(name, update)| {
+                (only.is_empty() || only.iter().any(|pattern| update.matches(name, pattern)))
+                    && !is_skipped(name, update)
+            })
+            .map(|(name, _)| name)
+            .collect();
+
+        // Pull in dependencies
+        let mut keep: HashSet<String> = HashSet::new();
+        let mut skipped_dependencies = Vec::new();
+        while let Some(name) = selected.pop() {
+            if !keep.insert(name.clone()) {
+                continue;
+            }
+
+            for dependency in &self.updates[name].depends {
+                if let Some((dependency, update)) = self.updates.get_key_value(dependency) {
+                    if is_skipped(dependency, update) {
+                        skipped_dependencies.push((name.clone(), dependency.clone()));
+                    } else {
+                        selected.push(dependency);
+                    }
+                }
+            }
+        }
+        skipped_dependencies.sort();
+
+        let removed: HashSet<String> = self
+            .updates
+            .keys()
+            .filter(|name| !keep.contains(*name))
+            .cloned()
+            .collect();
+
+        self.updates.retain(|name, _| keep.contains(name));
+        for update in self.updates.values_mut() {
+            update.depends.retain(|name| !removed.contains(name));
+            update.conflicts.retain(|name| !removed.contains(name));
+        }
+
+        FilterProblems {
+            unmatched,
+            skipped_dependencies,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(depends: &[&str], groups: &[&str]) -> UpdateConfig {
+        UpdateConfig {
+            kind: UpdateKind::default(),
+            input: false,
+            root: false,
+            prompts: Vec::new(),
+            pty: false,
+            conflicts: Vec::new(),
+            depends: depends.iter().map(|name| name.to_string()).collect(),
+            groups: groups.iter().map(|group| group.to_string()).collect(),
+            priority: 0,
+            timeout: None,
+            retries: 0,
+            retry_delay: 0,
+            retry_backoff: false,
+            if_missing: IfMissing::default(),
+            when: When::default(),
+            program: Program::new("true"),
+        }
+    }
+
+    /// a depends on b which depends on c, d is in group g with a
+    fn config() -> Config {
+        Config {
+            updater: UpdaterConfig::default(),
+            updates: HashMap::from([
+                ("a".to_string(), update(&["b"], &["g"])),
+                ("b".to_string(), update(&["c"], &[])),
+                ("c".to_string(), update(&[], &[])),
+                ("d".to_string(), update(&[], &["g"])),
+            ]),
+        }
+    }
+
+    fn strings(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn names(config: &Config) -> Vec<&str> {
+        let mut names: Vec<_> = config.updates.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn no_filter_keeps_everything() {
+        let mut config = config();
+        assert!(config.filter(&[], &[]).is_empty());
+        assert_eq!(names(&config), ["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn only_pulls_in_dependencies_transitively() {
+        let mut config = config();
+        assert!(config.filter(&strings(&["a"]), &[]).is_empty());
+        assert_eq!(names(&config), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn groups_select_their_updates() {
+        let mut config = config();
+        assert!(config.filter(&[], &strings(&["g"])).is_empty());
+        assert_eq!(names(&config), ["b", "c"]);
+    }
+
+    #[test]
+    fn skip_removes_references() {
+        let mut config = config();
+        config.updates.get_mut("c").unwrap().conflicts = strings(&["d"]);
+        assert!(config.filter(&[], &strings(&["d"])).is_empty());
+        assert_eq!(names(&config), ["a", "b", "c"]);
+        assert!(config.updates["c"].conflicts.is_empty());
+    }
+
+    #[test]
+    fn skipping_a_dependency_is_a_problem() {
+        let mut config = config();
+        let problems = config.filter(&strings(&["g"]), &strings(&["c"]));
+        assert_eq!(
+            problems.skipped_dependencies,
+            [("b".to_string(), "c".to_string())]
+        );
+    }
+
+    #[test]
+    fn unmatched_names_are_returned() {
+        let mut config = config();
+        let problems = config.filter(&strings(&["a", "nope"]), &strings(&["missing"]));
+        assert_eq!(problems.unmatched, strings(&["nope", "missing"]));
+    }
+}
@@ -5,4 +5,40 @@ pub enum UpdateKind {
     #[default]
     Default,
     Paru,
+    /// Forward input to the update when its output matches one of its prompts
+    Interactive,
+}
+
+/// A line of output that asks for input
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Prompt {
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub pattern: PromptPattern,
+    /// Don't echo the answer as it is typed (e.g. for a password)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub secret: bool,
+}
+
+/// A pattern matching a line of output that asks for input
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum PromptPattern {
+    /// The line starts with this text
+    Prefix(String),
+    /// The line matches this regular expression
+    Regex(String),
+}
+
+/// What to do when an update's executable can't be found
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum IfMissing {
+    /// The update is an error
+    #[default]
+    Error,
+    /// The update is skipped, it doesn't fail the run
+    Skip,
 }
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use crate::util::default_true;
 
 /// Represents details needed to start a program.
@@ -56,4 +58,50 @@ impl Program {
 
         self
     }
+
+    /// The command line to run the program, quoted for a POSIX shell
+    pub fn command_line(&self) -> String {
+        std::iter::once(&self.exe)
+            .chain(self.argv.iter().flatten())
+            .map(|arg| shell_quote(arg))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Quote an argument for a POSIX shell (if needed)
+pub fn shell_quote(arg: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_-./=:,+@%".contains(c);
+
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Conditions that must all be met for an update to run, checked when the update is started
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct When {
+    /// Files that must exist
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub file_exists: Vec<String>,
+    /// Executables that must be in PATH
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub in_path: Vec<String>,
+    /// Fields of /etc/os-release and the value they must have, fields that are lists (e.g.
+    /// `ID_LIKE`) only need to contain the value
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub os_release: BTreeMap<String, String>,
+    /// Hostnames the update runs on (any of them)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub hostname: Vec<String>,
+    /// Environment variables that must be set
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub env: Vec<String>,
+    /// Shell command that must exit successfully (killed after 30 seconds)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub probe: Option<String>,
 }
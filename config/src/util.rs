@@ -5,3 +5,7 @@ pub const fn default_true() -> bool {
 pub const fn default_two() -> usize {
     2
 }
+
+pub const fn default_ten() -> usize {
+    10
+}
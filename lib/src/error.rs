@@ -11,10 +11,29 @@ pub enum ErrorKind {
 
 pub struct Error {
     kind: ErrorKind,
-    source: Box<dyn std::error::Error + 'static>,
+    source: Box<dyn std::error::Error + Send + Sync + 'static>,
     backtrace: Backtrace,
 }
 
+/// What was being done when an error happened, with the error as its source
+#[derive(Debug)]
+pub struct Context {
+    message: String,
+    source: Box<dyn std::error::Error + Send + Sync + 'static>,
+}
+
+impl std::fmt::Display for Context {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Context {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:?}( {} )", self.kind, self.source)
@@ -33,6 +52,29 @@ impl std::fmt::Debug for Error {
     }
 }
 
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn backtrace(&self) -> &Backtrace {
+        &self.backtrace
+    }
+
+    /// The messages of the error and everything that caused it, e.g. "Unable to start "paru": No
+    /// such file or directory (os error 2)"
+    pub fn chain(&self) -> String {
+        let mut messages = Vec::new();
+        let mut error: Option<&(dyn std::error::Error + 'static)> = Some(self.source.as_ref());
+        while let Some(e) = error {
+            messages.push(e.to_string());
+            error = e.source();
+        }
+
+        messages.join(": ")
+    }
+}
+
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         Some(self.source.as_ref())
@@ -40,13 +82,28 @@ impl std::error::Error for Error {
 }
 
 impl ErrorKind {
-    pub fn context<E: Into<Box<dyn std::error::Error + 'static>>>(self, context: E) -> Error {
+    pub fn context<E: Into<Box<dyn std::error::Error + Send + Sync + 'static>>>(
+        self,
+        context: E,
+    ) -> Error {
         Error {
             kind: self,
             source: context.into(),
             backtrace: Backtrace::capture(),
         }
     }
+
+    /// An error caused by another error while doing what the message describes
+    pub fn context_with_source<E: Into<Box<dyn std::error::Error + Send + Sync + 'static>>>(
+        self,
+        message: impl Into<String>,
+        source: E,
+    ) -> Error {
+        self.context(Context {
+            message: message.into(),
+            source: source.into(),
+        })
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,77 @@
+use std::{
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Mutex,
+    },
+    time::SystemTime,
+};
+
+use crate::{types::*, update::output::OutputLine};
+
+/// Something that happened while updating
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub time: SystemTime,
+    pub kind: EventKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventKind {
+    /// An update changed state
+    StateChanged {
+        id: UpdateId,
+        from: State,
+        to: State,
+    },
+    /// An update is done and won't be retried
+    UpdateFinished { id: UpdateId, state: State },
+    /// An update started using the terminal's input
+    InputAcquired { id: UpdateId },
+    /// An update stopped using the terminal's input
+    InputReleased { id: UpdateId },
+    /// An update output a line
+    Output { id: UpdateId, line: OutputLine },
+    /// Every update is done
+    RunFinished,
+}
+
+impl Event {
+    /// The update the event is about (if any)
+    pub fn update(&self) -> Option<UpdateId> {
+        match self.kind {
+            EventKind::StateChanged { id, .. }
+            | EventKind::UpdateFinished { id, .. }
+            | EventKind::InputAcquired { id }
+            | EventKind::InputReleased { id }
+            | EventKind::Output { id, .. } => Some(id),
+            EventKind::RunFinished => None,
+        }
+    }
+}
+
+/// Sends events to everything that has subscribed
+#[derive(Debug, Default)]
+pub(crate) struct EventBus {
+    subscribers: Mutex<Vec<Sender<Event>>>,
+}
+
+impl EventBus {
+    pub(crate) fn subscribe(&self) -> Receiver<Event> {
+        let (tx, rx) = channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    pub(crate) fn emit(&self, kind: EventKind) {
+        let event = Event {
+            time: SystemTime::now(),
+            kind,
+        };
+
+        // Forget subscribers that have stopped listening
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
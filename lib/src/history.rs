@@ -0,0 +1,163 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, ErrorKind as IOErrorKind, Write},
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::{
+    error::{context, ErrorKind::IOError, Result},
+    Update,
+};
+
+/// How long each update took the last time it ran, keyed by update name.
+///
+/// Stored as one `<milliseconds> <name>` pair per line.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    durations: HashMap<String, Duration>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load history from a file, a missing file is treated as empty history
+    pub fn load(path: impl AsRef<Path>) -> Result<History> {
+        let path = path.as_ref();
+
+        let file = match std::fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == IOErrorKind::NotFound => return Ok(History::new()),
+            Err(e) => return Err(IOError.context(e)),
+        };
+
+        let mut history = History::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| IOError.context(e))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let (millis, name) = line.split_once(' ').ok_or_else(|| {
+                context!(IOError, "Invalid history line in {:?}: {:?}", path, line)
+            })?;
+            let millis: u64 = millis
+                .parse()
+                .map_err(|_| context!(IOError, "Invalid duration in {:?}: {:?}", path, line))?;
+
+            history.record(name, Duration::from_millis(millis));
+        }
+
+        Ok(history)
+    }
+
+    /// Save history to a file, creating parent directories as needed
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| IOError.context(e))?;
+        }
+
+        let mut names: Vec<_> = self.durations.keys().collect();
+        names.sort();
+
+        let mut file = std::fs::File::create(path).map_err(|e| IOError.context(e))?;
+        for name in names {
+            writeln!(file, "{} {}", self.durations[name].as_millis(), name)
+                .map_err(|e| IOError.context(e))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<Duration> {
+        self.durations.get(name).copied()
+    }
+
+    pub fn record(&mut self, name: impl Into<String>, duration: Duration) {
+        self.durations.insert(name.into(), duration);
+    }
+
+    /// Record the durations of all updates that produced output
+    pub fn record_updates(&mut self, updates: &[Arc<Update>]) {
+        for update in updates {
+            if let Some(output) = update.output() {
+                self.record(update.name.clone(), output.duration);
+            }
+        }
+    }
+
+    /// The mean of all recorded durations
+    pub fn mean(&self) -> Option<Duration> {
+        if self.durations.is_empty() {
+            return None;
+        }
+
+        let total: Duration = self.durations.values().sum();
+        Some(total / self.durations.len() as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A history file path that is unique to the test
+    fn path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("parallel-update-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut history = History::new();
+        history.record("a", Duration::from_millis(1500));
+        history.record("name with spaces", Duration::from_secs(3));
+
+        let path = path("history-round-trip");
+        history.save(&path).unwrap();
+        let loaded = History::load(&path);
+        let _ = std::fs::remove_file(&path);
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.get("a"), Some(Duration::from_millis(1500)));
+        assert_eq!(loaded.get("name with spaces"), Some(Duration::from_secs(3)));
+        assert_eq!(loaded.durations.len(), 2);
+    }
+
+    #[test]
+    fn load_missing_file() {
+        let history = History::load(path("history-missing")).unwrap();
+
+        assert_eq!(history.mean(), None);
+    }
+
+    #[test]
+    fn load_malformed_lines() {
+        for (contents, valid) in [
+            ("100 a\n\n  \n200 b\n", true),
+            ("100\n", false),
+            ("abc a\n", false),
+            ("-1 a\n", false),
+        ] {
+            let path = path("history-malformed");
+            std::fs::write(&path, contents).unwrap();
+            let loaded = History::load(&path);
+            let _ = std::fs::remove_file(&path);
+
+            assert_eq!(loaded.is_ok(), valid, "{:?}", contents);
+        }
+    }
+
+    #[test]
+    fn mean() {
+        let mut history = History::new();
+        history.record("a", Duration::from_secs(1));
+        history.record("b", Duration::from_secs(3));
+
+        assert_eq!(history.mean(), Some(Duration::from_secs(2)));
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    process::{Child, Command, Stdio},
+    sync::{mpsc::Receiver, Arc},
+    thread::JoinHandle,
+};
+
+use parallel_update_config::config::UpdaterConfig;
+
+use crate::{
+    events::{Event, EventKind},
+    types::*,
+    update::Update,
+};
+
+/// Shell commands run when something happens during a run.
+///
+/// Hooks are run with `sh -c` and environment variables describing what happened:
+/// - `PARALLEL_UPDATE_EVENT`: `input_needed`, `update_done` or `run_done`
+/// - `PARALLEL_UPDATE_NAME`: name of the update (not for `run_done`)
+/// - `PARALLEL_UPDATE_STATE`: state the update finished in (`update_done` only)
+/// - `PARALLEL_UPDATE_EXIT_CODE`: exit code of a failed update (`update_done` only)
+/// - `PARALLEL_UPDATE_TOTAL`, `PARALLEL_UPDATE_SUCCEEDED`, `PARALLEL_UPDATE_SKIPPED` and
+///   `PARALLEL_UPDATE_FAILED`: number of updates (`run_done` only)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Hooks {
+    /// Run when an update starts using the terminal's input
+    pub on_input_needed: Option<String>,
+    /// Run when an update is done (and won't be retried)
+    pub on_update_done: Option<String>,
+    /// Run once every update is done
+    pub on_run_done: Option<String>,
+}
+
+impl Hooks {
+    pub fn from_config(config: &UpdaterConfig) -> Hooks {
+        Hooks {
+            on_input_needed: config.on_input_needed.clone(),
+            on_update_done: config.on_update_done.clone(),
+            on_run_done: config.on_run_done.clone(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.on_input_needed.is_none()
+            && self.on_update_done.is_none()
+            && self.on_run_done.is_none()
+    }
+
+    /// Start hooks for events until the run is finished, hooks run alongside each other (so a
+    /// slow hook doesn't hold up the rest) and the thread ends once every hook has exited
+    pub(crate) fn start(
+        self,
+        updates: Vec<Arc<Update>>,
+        events: Receiver<Event>,
+    ) -> JoinHandle<()> {
+        std::thread::spawn(move || {
+            let mut running: Vec<Child> = Vec::new();
+            for event in events {
+                // Don't leave hooks that have exited as zombies until the run is finished
+                running.retain_mut(|child| matches!(child.try_wait(), Ok(None)));
+
+                let child = match event.kind {
+                    EventKind::InputAcquired { id } => {
+                        self.on_input_needed.as_ref().and_then(|hook| {
+                            start_hook(hook, "input_needed", [("NAME", updates[id.0].name.clone())])
+                        })
+                    }
+                    EventKind::UpdateFinished { id, state } => {
+                        self.on_update_done.as_ref().and_then(|hook| {
+                            let exit_code = match state {
+                                State::Failed(code) => code.to_string(),
+                                _ => String::new(),
+                            };
+                            start_hook(
+                                hook,
+                                "update_done",
+                                [
+                                    ("NAME", updates[id.0].name.clone()),
+                                    ("STATE", state.name().to_string()),
+                                    ("EXIT_CODE", exit_code),
+                                ],
+                            )
+                        })
+                    }
+                    EventKind::RunFinished => {
+                        if let Some(hook) = self.on_run_done.as_ref() {
+                            let states: Vec<_> =
+                                updates.iter().map(|update| update.state.get()).collect();
+                            let succeeded = states
+                                .iter()
+                                .filter(|state| **state == State::Success)
+                                .count();
+                            let skipped = states
+                                .iter()
+                                .filter(|state| matches!(state, State::Skipped(_)))
+                                .count();
+                            running.extend(start_hook(
+                                hook,
+                                "run_done",
+                                [
+                                    ("TOTAL", states.len().to_string()),
+                                    ("SUCCEEDED", succeeded.to_string()),
+                                    ("SKIPPED", skipped.to_string()),
+                                    ("FAILED", (states.len() - succeeded - skipped).to_string()),
+                                ],
+                            ));
+                        }
+                        break;
+                    }
+                    _ => None,
+                };
+                running.extend(child);
+            }
+
+            for mut child in running {
+                let _ = child.wait();
+            }
+        })
+    }
+}
+
+/// Start a hook without waiting for it
+fn start_hook<const N: usize>(hook: &str, event: &str, vars: [(&str, String); N]) -> Option<Child> {
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(hook)
+        .env("PARALLEL_UPDATE_EVENT", event)
+        .envs(
+            vars.iter()
+                .map(|(key, value)| (format!("PARALLEL_UPDATE_{}", key), value)),
+        )
+        // Hooks must not take input from updates
+        .stdin(Stdio::null())
+        .stdout(Stdio::null());
+
+    // A failing hook shouldn't affect the updates
+    command.spawn().ok()
+}
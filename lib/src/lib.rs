@@ -1,7 +1,18 @@
 pub mod error;
+pub mod events;
+pub mod history;
+pub mod hooks;
+pub mod logs;
+mod scheduler;
+pub mod sudo;
+pub mod time;
 pub mod types;
 pub mod update;
 mod updater;
 
+pub use history::History;
+pub use hooks::Hooks;
+pub use logs::RunLogs;
+pub use sudo::SudoKeepAlive;
 pub use update::Update;
-pub use updater::Updater;
+pub use updater::{CancelHandle, Plan, Updater};
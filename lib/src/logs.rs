@@ -0,0 +1,143 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{
+    error::{ErrorKind::IOError, Result},
+    time::rfc3339,
+};
+
+/// Log files for a single run, stored in a directory named after the time the run started
+#[derive(Debug, Clone)]
+pub struct RunLogs {
+    dir: PathBuf,
+}
+
+impl RunLogs {
+    /// Create the directory for a run starting now inside the log directory
+    pub fn create(log_dir: impl AsRef<Path>) -> Result<RunLogs> {
+        let name = rfc3339(SystemTime::now()).replace(':', "-");
+        let dir = log_dir.as_ref().join(name);
+
+        std::fs::create_dir_all(&dir).map_err(|e| IOError.context(e))?;
+
+        Ok(RunLogs { dir })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The log file for an update
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.log", name.replace('/', "_")))
+    }
+
+    /// Remove old runs from the log directory, keeping this run and the newest runs before it so
+    /// that at most `keep` runs are left.
+    ///
+    /// Only directories named like runs are removed, anything else in the log directory is left.
+    pub fn prune(&self, keep: usize) -> Result<()> {
+        let log_dir = match self.dir.parent() {
+            Some(log_dir) => log_dir,
+            None => return Ok(()),
+        };
+
+        let mut runs = Vec::new();
+        for entry in std::fs::read_dir(log_dir).map_err(|e| IOError.context(e))? {
+            let entry = entry.map_err(|e| IOError.context(e))?;
+            let path = entry.path();
+            if entry.file_type().map_err(|e| IOError.context(e))?.is_dir()
+                && is_run_name(&entry.file_name().to_string_lossy())
+                && path != self.dir
+            {
+                runs.push(path);
+            }
+        }
+
+        // Run directories are named by time so sort oldest first
+        runs.sort();
+
+        // This run is one of the runs kept
+        let remove = runs.len().saturating_sub(keep.saturating_sub(1));
+        for run in runs.into_iter().take(remove) {
+            std::fs::remove_dir_all(run).map_err(|e| IOError.context(e))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether a directory name is a run's start time (e.g. "2024-01-02T03-04-05.678Z")
+fn is_run_name(name: &str) -> bool {
+    const PATTERN: &[u8] = b"0000-00-00T00-00-00.000Z";
+
+    name.len() == PATTERN.len()
+        && name.bytes().zip(PATTERN).all(|(c, &p)| match p {
+            b'0' => c.is_ascii_digit(),
+            _ => c == p,
+        })
+}
+
+/// Open a log file for appending (each attempt is appended)
+pub(crate) fn open(path: &Path) -> Option<File> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_names() {
+        assert!(is_run_name("2024-01-02T03-04-05.678Z"));
+        assert!(!is_run_name(".keepme"));
+        assert!(!is_run_name("2024-01-02T03:04:05.678Z"));
+        assert!(!is_run_name("2024-01-02T03-04-05.678Z-old"));
+    }
+
+    #[test]
+    fn prune_only_removes_old_runs() {
+        let log_dir =
+            std::env::temp_dir().join(format!("parallel-update-logs-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&log_dir);
+        for name in [
+            "2000-01-01T00-00-00.000Z",
+            "2000-01-02T00-00-00.000Z",
+            ".keepme",
+            "important",
+        ] {
+            std::fs::create_dir_all(log_dir.join(name)).unwrap();
+        }
+        // A run that sorts before the others
+        let logs = RunLogs {
+            dir: log_dir.join("1999-01-01T00-00-00.000Z"),
+        };
+        std::fs::create_dir_all(logs.dir()).unwrap();
+
+        logs.prune(2).unwrap();
+
+        let mut left: Vec<_> = std::fs::read_dir(&log_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        left.sort();
+        std::fs::remove_dir_all(&log_dir).unwrap();
+
+        assert_eq!(
+            left,
+            [
+                ".keepme",
+                "1999-01-01T00-00-00.000Z",
+                "2000-01-02T00-00-00.000Z",
+                "important"
+            ]
+        );
+    }
+}
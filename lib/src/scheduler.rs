@@ -0,0 +1,456 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, BinaryHeap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{types::*, Update};
+
+/// Order in which updates are started, the greatest key is started first
+pub(crate) type ScheduleKey = (i32, Duration, Reverse<usize>);
+
+/// Keeps track of which updates can be started so that each scheduling decision only looks at
+/// the updates affected by what changed.
+///
+/// The scheduler is told when updates are started and when they finish, everything else is
+/// derived from that: an update is ready once all its dependencies have succeeded, no conflicting
+/// update is running and it isn't waiting to be retried.
+pub(crate) struct Scheduler<'a> {
+    updates: &'a [Arc<Update>],
+    keys: Vec<ScheduleKey>,
+    /// Updates that depend on each update
+    dependents: Vec<Vec<UpdateId>>,
+    /// Number of dependencies of each update that haven't succeeded yet
+    remaining_depends: Vec<usize>,
+    /// Number of conflicting updates of each update that are running
+    running_conflicts: Vec<usize>,
+    /// Updates waiting for their retry delay to pass
+    retrying: BinaryHeap<Reverse<(Instant, usize)>>,
+    /// Whether each update is waiting for its retry delay to pass
+    waiting_to_retry: Vec<bool>,
+    /// Ready updates that don't need input
+    ready: BTreeSet<ScheduleKey>,
+    /// Ready updates that need input
+    ready_input: BTreeSet<ScheduleKey>,
+    running: usize,
+    finished: usize,
+    cancelled: bool,
+}
+
+impl<'a> Scheduler<'a> {
+    pub(crate) fn new(updates: &'a [Arc<Update>], keys: Vec<ScheduleKey>) -> Scheduler<'a> {
+        let mut dependents = vec![Vec::new(); updates.len()];
+        for update in updates {
+            for dependency in &update.info.depends {
+                dependents[dependency.0].push(update.id);
+            }
+        }
+
+        let mut scheduler = Scheduler {
+            updates,
+            keys,
+            dependents,
+            remaining_depends: updates
+                .iter()
+                .map(|update| update.info.depends.len())
+                .collect(),
+            running_conflicts: vec![0; updates.len()],
+            retrying: BinaryHeap::new(),
+            waiting_to_retry: vec![false; updates.len()],
+            ready: BTreeSet::new(),
+            ready_input: BTreeSet::new(),
+            running: 0,
+            finished: 0,
+            cancelled: false,
+        };
+
+        for update in updates {
+            scheduler.make_ready_if_possible(update.id);
+        }
+
+        scheduler
+    }
+
+    fn queue(&mut self, id: UpdateId) -> &mut BTreeSet<ScheduleKey> {
+        if self.updates[id.0].info.input {
+            &mut self.ready_input
+        } else {
+            &mut self.ready
+        }
+    }
+
+    fn make_ready_if_possible(&mut self, id: UpdateId) {
+        if self.remaining_depends[id.0] == 0
+            && self.running_conflicts[id.0] == 0
+            && !self.waiting_to_retry[id.0]
+            && self.updates[id.0].state.get() == State::Pending
+        {
+            let key = self.keys[id.0];
+            self.queue(id).insert(key);
+        }
+    }
+
+    /// Take the ready update that should be started next
+    pub(crate) fn next(&mut self, stdin_in_use: bool) -> Option<UpdateId> {
+        let queue = match (self.ready.last(), self.ready_input.last()) {
+            (Some(key), Some(input_key)) if !stdin_in_use && input_key > key => {
+                &mut self.ready_input
+            }
+            (None, Some(_)) if !stdin_in_use => &mut self.ready_input,
+            _ => &mut self.ready,
+        };
+
+        queue.pop_last().map(|(_, _, Reverse(id))| UpdateId(id))
+    }
+
+    /// An update has been started, conflicting updates can't be started until it finishes
+    pub(crate) fn started(&mut self, id: UpdateId) {
+        self.running += 1;
+
+        for conflict in &self.updates[id.0].info.conflicts {
+            self.running_conflicts[conflict.0] += 1;
+            let key = self.keys[conflict.0];
+            self.queue(*conflict).remove(&key);
+        }
+    }
+
+    /// An update's runner has returned
+    pub(crate) fn finished(&mut self, id: UpdateId) {
+        self.running -= 1;
+
+        for conflict in &self.updates[id.0].info.conflicts {
+            self.running_conflicts[conflict.0] -= 1;
+            self.make_ready_if_possible(*conflict);
+        }
+
+        let update = &self.updates[id.0];
+        if update.will_retry() {
+            if !self.cancelled {
+                self.retrying.push(Reverse((update.retry_at(), id.0)));
+                self.waiting_to_retry[id.0] = true;
+                return;
+            }
+            update.stop_retrying();
+        }
+
+        self.finished += 1;
+
+        if update.state.get().satisfies_dependents() {
+            for i in 0..self.dependents[id.0].len() {
+                let dependent = self.dependents[id.0][i];
+                self.remaining_depends[dependent.0] -= 1;
+                self.make_ready_if_possible(dependent);
+            }
+        } else {
+            self.ignore_dependents(id);
+        }
+    }
+
+    /// Mark everything depending (directly or indirectly) on a failed update as ignored
+    fn ignore_dependents(&mut self, id: UpdateId) {
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            for dependent in &self.dependents[id.0] {
+                let update = &self.updates[dependent.0];
+                if update.state.get() == State::Pending {
+                    update.set_state(State::Ignored);
+                    self.finished += 1;
+                    stack.push(*dependent);
+                }
+            }
+        }
+    }
+
+    /// Make updates whose retry delay has passed ready, returning when the next one will be
+    pub(crate) fn release_retries(&mut self, now: Instant) -> Option<Instant> {
+        while let Some(Reverse((at, i))) = self.retrying.peek().copied() {
+            if at > now {
+                return Some(at);
+            }
+            self.retrying.pop();
+            self.waiting_to_retry[i] = false;
+            self.updates[i].set_state(State::Pending);
+            self.make_ready_if_possible(UpdateId(i));
+        }
+
+        None
+    }
+
+    /// Stop retrying failed updates and mark every update that hasn't started as cancelled
+    pub(crate) fn cancel(&mut self) {
+        self.cancelled = true;
+        self.ready.clear();
+        self.ready_input.clear();
+
+        // Updates waiting to be retried have run, they keep the state their last attempt ended in
+        for Reverse((_, i)) in std::mem::take(&mut self.retrying) {
+            self.waiting_to_retry[i] = false;
+            self.updates[i].stop_retrying();
+            self.finished += 1;
+            self.ignore_dependents(UpdateId(i));
+        }
+
+        for update in self.updates {
+            if update.state.get() == State::Pending {
+                update.set_state(State::Cancelled);
+                self.finished += 1;
+            }
+        }
+    }
+
+    /// Mark every update that hasn't started as unschedulable with the reason it couldn't be
+    /// started
+    pub(crate) fn mark_unschedulable(&mut self) {
+        let satisfied: HashSet<_> = self
+            .updates
+            .iter()
+            .filter(|update| update.state.get().satisfies_dependents())
+            .map(|update| update.id)
+            .collect();
+
+        for update in self.updates {
+            if update.state.get() != State::Pending {
+                continue;
+            }
+
+            let reason = if let Some(dependency) = update
+                .info
+                .depends
+                .iter()
+                .find(|id| !satisfied.contains(id))
+            {
+                Blocked::Dependency(*dependency)
+            } else if let Some(conflict) = update
+                .info
+                .conflicts
+                .iter()
+                .find(|id| self.updates[id.0].state.get().is_running())
+            {
+                Blocked::Conflict(*conflict)
+            } else {
+                Blocked::NoThreads
+            };
+
+            update.set_state(State::Unschedulable(reason));
+            self.finished += 1;
+        }
+    }
+
+    pub(crate) fn running(&self) -> usize {
+        self.running
+    }
+
+    /// Whether anything is waiting to be retried
+    pub(crate) fn has_retries(&self) -> bool {
+        !self.retrying.is_empty()
+    }
+
+    pub(crate) fn all_done(&self) -> bool {
+        self.finished == self.updates.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::atomic::Ordering, time::Duration};
+
+    use super::*;
+
+    fn update(id: usize, depends: &[usize], conflicts: &[usize], retries: usize) -> Arc<Update> {
+        let mut update = Update::for_test(id, depends, conflicts);
+        update.info.retries = retries;
+        update.info.retry_delay = Duration::from_secs(10);
+        Arc::new(update)
+    }
+
+    fn keys(updates: &[Arc<Update>]) -> Vec<ScheduleKey> {
+        updates
+            .iter()
+            .map(|update| (0, Duration::ZERO, Reverse(update.id.0)))
+            .collect()
+    }
+
+    /// Start the next ready update and finish it in the given state
+    fn run_next(scheduler: &mut Scheduler, updates: &[Arc<Update>], state: State) -> UpdateId {
+        let id = scheduler.next(false).expect("an update should be ready");
+        scheduler.started(id);
+        updates[id.0].attempts.fetch_add(1, Ordering::SeqCst);
+        updates[id.0].set_state(state);
+        scheduler.finished(id);
+        id
+    }
+
+    #[test]
+    fn starts_in_key_order() {
+        let updates = vec![update(0, &[], &[], 0), update(1, &[], &[], 0)];
+        let mut keys = keys(&updates);
+        keys[1].0 = 1;
+        let mut scheduler = Scheduler::new(&updates, keys);
+
+        assert_eq!(scheduler.next(false), Some(UpdateId(1)));
+        assert_eq!(scheduler.next(false), Some(UpdateId(0)));
+        assert_eq!(scheduler.next(false), None);
+    }
+
+    #[test]
+    fn dependents_wait_for_success() {
+        let updates = vec![update(0, &[], &[], 0), update(1, &[0], &[], 0)];
+        let mut scheduler = Scheduler::new(&updates, keys(&updates));
+
+        assert_eq!(
+            run_next(&mut scheduler, &updates, State::Success),
+            UpdateId(0)
+        );
+        assert_eq!(
+            run_next(&mut scheduler, &updates, State::Success),
+            UpdateId(1)
+        );
+        assert!(scheduler.all_done());
+    }
+
+    #[test]
+    fn skipped_dependencies_are_satisfied() {
+        let updates = vec![update(0, &[], &[], 0), update(1, &[0], &[], 0)];
+        let mut scheduler = Scheduler::new(&updates, keys(&updates));
+
+        let skipped = State::Skipped(SkipReason::MissingExecutable);
+        assert_eq!(run_next(&mut scheduler, &updates, skipped), UpdateId(0));
+        assert_eq!(scheduler.next(false), Some(UpdateId(1)));
+    }
+
+    #[test]
+    fn conflicts_are_not_run_together() {
+        let updates = vec![update(0, &[], &[1], 0), update(1, &[], &[0], 0)];
+        let mut scheduler = Scheduler::new(&updates, keys(&updates));
+
+        let first = scheduler.next(false).unwrap();
+        scheduler.started(first);
+        assert_eq!(scheduler.next(false), None);
+
+        updates[first.0].set_state(State::Success);
+        scheduler.finished(first);
+        assert_eq!(scheduler.next(false), Some(UpdateId(1)));
+    }
+
+    #[test]
+    fn input_updates_wait_for_input() {
+        let mut input = Arc::try_unwrap(update(0, &[], &[], 0)).unwrap();
+        input.info.input = true;
+        let updates = vec![Arc::new(input), update(1, &[], &[], 0)];
+        let mut scheduler = Scheduler::new(&updates, keys(&updates));
+
+        assert_eq!(scheduler.next(true), Some(UpdateId(1)));
+        assert_eq!(scheduler.next(true), None);
+        assert_eq!(scheduler.next(false), Some(UpdateId(0)));
+    }
+
+    #[test]
+    fn failures_ignore_dependents_transitively() {
+        let updates = vec![
+            update(0, &[], &[], 0),
+            update(1, &[0], &[], 0),
+            update(2, &[1], &[], 0),
+        ];
+        let mut scheduler = Scheduler::new(&updates, keys(&updates));
+
+        run_next(&mut scheduler, &updates, State::Failed(1));
+        assert_eq!(updates[1].state.get(), State::Ignored);
+        assert_eq!(updates[2].state.get(), State::Ignored);
+        assert_eq!(scheduler.next(false), None);
+        assert!(scheduler.all_done());
+    }
+
+    #[test]
+    fn retries_are_released_after_their_delay() {
+        let updates = vec![update(0, &[], &[], 1)];
+        let mut scheduler = Scheduler::new(&updates, keys(&updates));
+
+        let now = Instant::now();
+        run_next(&mut scheduler, &updates, State::Failed(1));
+        assert!(scheduler.has_retries());
+        assert!(!scheduler.all_done());
+        // The update keeps its failed state while it waits
+        assert_eq!(updates[0].state.get(), State::Failed(1));
+
+        assert!(scheduler.release_retries(now).is_some());
+        assert_eq!(scheduler.next(false), None);
+
+        assert_eq!(
+            scheduler.release_retries(now + Duration::from_secs(11)),
+            None
+        );
+        assert_eq!(updates[0].state.get(), State::Pending);
+
+        run_next(&mut scheduler, &updates, State::Failed(1));
+        assert!(!scheduler.has_retries());
+        assert!(scheduler.all_done());
+    }
+
+    #[test]
+    fn cancel_only_cancels_updates_that_never_ran() {
+        let updates = vec![
+            update(0, &[], &[], 3),
+            update(1, &[], &[], 0),
+            update(2, &[], &[], 0),
+        ];
+        let mut scheduler = Scheduler::new(&updates, keys(&updates));
+
+        run_next(&mut scheduler, &updates, State::Failed(2));
+        let running = scheduler.next(false).unwrap();
+        scheduler.started(running);
+        updates[running.0].set_state(State::Running);
+
+        scheduler.cancel();
+        assert_eq!(updates[0].state.get(), State::Failed(2));
+        assert!(!updates[0].will_retry());
+        assert_eq!(updates[2].state.get(), State::Cancelled);
+        assert_eq!(scheduler.next(false), None);
+        assert!(!scheduler.all_done());
+
+        // Running updates aren't retried once cancelled
+        updates[running.0].set_state(State::Killed(2));
+        scheduler.finished(running);
+        assert!(scheduler.all_done());
+    }
+
+    #[test]
+    fn cancelled_failures_are_not_retried() {
+        let updates = vec![update(0, &[], &[], 3)];
+        let mut scheduler = Scheduler::new(&updates, keys(&updates));
+
+        let id = scheduler.next(false).unwrap();
+        scheduler.started(id);
+        updates[0].set_state(State::Running);
+        scheduler.cancel();
+
+        updates[0].attempts.fetch_add(1, Ordering::SeqCst);
+        updates[0].set_state(State::Failed(2));
+        scheduler.finished(id);
+        assert_eq!(updates[0].state.get(), State::Failed(2));
+        assert!(!scheduler.has_retries());
+        assert!(scheduler.all_done());
+    }
+
+    #[test]
+    fn stalled_updates_are_unschedulable() {
+        let updates = vec![
+            update(0, &[], &[], 0),
+            update(1, &[0], &[], 0),
+            update(2, &[], &[], 0),
+        ];
+        let mut scheduler = Scheduler::new(&updates, keys(&updates));
+
+        // Nothing is started, as if there were no threads
+        scheduler.mark_unschedulable();
+        assert_eq!(
+            updates[0].state.get(),
+            State::Unschedulable(Blocked::NoThreads)
+        );
+        assert_eq!(
+            updates[1].state.get(),
+            State::Unschedulable(Blocked::Dependency(UpdateId(0)))
+        );
+        assert!(scheduler.all_done());
+    }
+}
@@ -0,0 +1,67 @@
+use std::{
+    process::{Command, Stdio},
+    sync::mpsc::{channel, RecvTimeoutError, Sender},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use crate::error::{
+    bail,
+    ErrorKind::{CommandOutput, CommandSpawn},
+    Result,
+};
+
+/// How often to refresh the cached credentials (sudo caches them for 5 minutes by default)
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Keeps sudo credentials cached until dropped, so that updates using sudo don't need to prompt
+/// for a password.
+#[derive(Debug)]
+pub struct SudoKeepAlive {
+    stop: Sender<()>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl SudoKeepAlive {
+    /// Prompt for the password once (`sudo -v`) then refresh the credentials in the background
+    pub fn start() -> Result<SudoKeepAlive> {
+        let status = Command::new("sudo")
+            .arg("-v")
+            .status()
+            .map_err(|e| CommandSpawn.context(e))?;
+        if !status.success() {
+            bail!(
+                CommandOutput,
+                "Unable to authenticate with sudo: {}",
+                status
+            );
+        }
+
+        let (stop, stopped) = channel();
+        let handle = std::thread::spawn(move || {
+            while stopped.recv_timeout(REFRESH_INTERVAL) == Err(RecvTimeoutError::Timeout) {
+                // Never prompt from the background
+                let _ = Command::new("sudo")
+                    .args(["-n", "-v"])
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status();
+            }
+        });
+
+        Ok(SudoKeepAlive {
+            stop,
+            handle: Some(handle),
+        })
+    }
+}
+
+impl Drop for SudoKeepAlive {
+    fn drop(&mut self) {
+        let _ = self.stop.send(());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
@@ -1,11 +1,27 @@
-use std::sync::{mpsc::Sender, Condvar, Mutex};
+use std::{
+    sync::{mpsc::Sender, Arc, Condvar, Mutex},
+    time::Duration,
+};
 
-use crate::error::ErrorKind;
+use parallel_update_config::{
+    primatives::{self, IfMissing, Prompt},
+    types::When,
+};
+use regex::Regex;
+
+use crate::{
+    error::{
+        Error,
+        ErrorKind::{self, InvalidConfig},
+        Result,
+    },
+    events::{EventBus, EventKind},
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct UpdateId(pub usize);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum State {
     /// Update is waiting to start
     Pending,
@@ -19,20 +35,153 @@ pub enum State {
     Failed(i32),
     /// Update encountered a rust error while running
     Error(ErrorKind),
+    /// The update's runner panicked (with this message)
+    Panicked(String),
+    /// Update was killed for running longer than its timeout
+    TimedOut,
+    /// Update was killed by a signal (e.g. one forwarded when the run was cancelled)
+    Killed(i32),
     /// Update wasn't able to run
     Ignored,
+    /// Update could never be started
+    Unschedulable(Blocked),
+    /// Update was never started because the run was cancelled
+    Cancelled,
+    /// Update wasn't run because it doesn't apply (this doesn't fail the run)
+    Skipped(SkipReason),
+}
+
+/// What prevented an update from being started
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Blocked {
+    /// A dependency never finished
+    Dependency(UpdateId),
+    /// A conflicting update never finished
+    Conflict(UpdateId),
+    /// The updater was not allowed any threads
+    NoThreads,
+}
+
+/// Why an update was skipped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The update's executable wasn't found
+    MissingExecutable,
+    /// A condition of the update wasn't met (described by the message)
+    Condition(String),
 }
 
 impl State {
     pub fn is_done(&self) -> bool {
         matches!(
             self,
-            State::Success | State::Failed(_) | State::Error(_) | State::Ignored
+            State::Success
+                | State::Failed(_)
+                | State::Error(_)
+                | State::Panicked(_)
+                | State::TimedOut
+                | State::Killed(_)
+                | State::Ignored
+                | State::Unschedulable(_)
+                | State::Cancelled
+                | State::Skipped(_)
         )
     }
     pub fn is_running(&self) -> bool {
         matches!(self, State::Starting | State::Running)
     }
+    /// Whether the update could succeed if it was run again
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, State::Failed(_) | State::TimedOut)
+    }
+    /// Whether updates that depend on this one can run
+    pub fn satisfies_dependents(&self) -> bool {
+        matches!(self, State::Success | State::Skipped(_))
+    }
+    /// Name of the state (without details) for reports and hooks
+    pub fn name(&self) -> &'static str {
+        match self {
+            State::Pending => "pending",
+            State::Starting => "starting",
+            State::Running => "running",
+            State::Success => "success",
+            State::Failed(_) => "failed",
+            State::Error(_) => "error",
+            State::Panicked(_) => "panicked",
+            State::TimedOut => "timed_out",
+            State::Killed(_) => "killed",
+            State::Ignored => "ignored",
+            State::Unschedulable(_) => "unschedulable",
+            State::Cancelled => "cancelled",
+            State::Skipped(_) => "skipped",
+        }
+    }
+}
+
+/// A compiled pattern matching output that asks for input
+#[derive(Debug, Clone)]
+pub enum PromptPattern {
+    /// The line starts with this text
+    Prefix(String),
+    /// The line matches this regular expression
+    Regex(Regex),
+}
+
+impl PromptPattern {
+    pub fn matches(&self, line: &str) -> bool {
+        match self {
+            PromptPattern::Prefix(prefix) => line.starts_with(prefix.as_str()),
+            PromptPattern::Regex(regex) => regex.is_match(line),
+        }
+    }
+}
+
+impl PartialEq for PromptPattern {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (PromptPattern::Prefix(a), PromptPattern::Prefix(b)) => a == b,
+            (PromptPattern::Regex(a), PromptPattern::Regex(b)) => a.as_str() == b.as_str(),
+            _ => false,
+        }
+    }
+}
+
+impl TryFrom<primatives::PromptPattern> for PromptPattern {
+    type Error = Error;
+
+    fn try_from(pattern: primatives::PromptPattern) -> Result<Self> {
+        Ok(match pattern {
+            primatives::PromptPattern::Prefix(prefix) => PromptPattern::Prefix(prefix),
+            primatives::PromptPattern::Regex(regex) => {
+                PromptPattern::Regex(Regex::new(&regex).map_err(|e| InvalidConfig.context(e))?)
+            }
+        })
+    }
+}
+
+/// A prompt that input is forwarded for
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputPrompt {
+    pub pattern: PromptPattern,
+    /// Don't echo the answer as it is typed (e.g. for a password)
+    pub secret: bool,
+}
+
+impl InputPrompt {
+    pub fn matches(&self, line: &str) -> bool {
+        self.pattern.matches(line)
+    }
+}
+
+impl TryFrom<Prompt> for InputPrompt {
+    type Error = Error;
+
+    fn try_from(prompt: Prompt) -> Result<Self> {
+        Ok(InputPrompt {
+            pattern: prompt.pattern.try_into()?,
+            secret: prompt.secret,
+        })
+    }
 }
 
 /// Represents relevent info about a program.
@@ -42,8 +191,26 @@ pub struct Info {
     pub input: bool,
     /// Whether the update uses a program that gives root (e.g. sudo)
     pub root: bool,
+    /// Prompts that input is forwarded for in order, input is released after the last prompt
+    pub prompts: Vec<InputPrompt>,
+    /// Run the update in a pseudo-terminal
+    pub pty: bool,
     /// Can run in parallel
     pub parallel: bool,
+    /// Scheduling priority, higher priority updates are started first
+    pub priority: i32,
+    /// How long the update may run for before it is killed
+    pub timeout: Option<Duration>,
+    /// How many times to retry the update if it fails
+    pub retries: usize,
+    /// How long to wait before retrying a failed update
+    pub retry_delay: Duration,
+    /// Double the retry delay after each failed attempt
+    pub retry_backoff: bool,
+    /// What to do when the executable isn't found
+    pub if_missing: IfMissing,
+    /// Conditions that must be met for the update to run
+    pub when: When,
     /// Updates that cannot run at the same time (order doesn't matter)
     pub conflicts: Vec<UpdateId>,
     /// Updates that must run before
@@ -65,14 +232,16 @@ impl SyncState {
         }
     }
 
-    pub(crate) fn set(&self, state: State) {
+    /// Set the state, returning the previous state
+    pub(crate) fn set(&self, state: State) -> State {
         let mut store = self.mutex.lock().unwrap();
-        *store = state;
+        let previous = std::mem::replace(&mut *store, state);
         self.condvar.notify_one();
+        previous
     }
 
     pub fn get(&self) -> State {
-        *self.mutex.lock().unwrap()
+        self.mutex.lock().unwrap().clone()
     }
 
     pub(crate) fn wait_until_not(&self, state: State) {
@@ -88,8 +257,79 @@ impl SyncState {
     }
 }
 
+/// Reasons for the scheduler to wake up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Notification {
+    /// An update's runner has returned
+    Finished(UpdateId),
+    /// An update released input
+    InputReleased(UpdateId),
+    /// The run was cancelled by a signal that should be forwarded to running updates
+    Cancel(i32),
+}
+
+/// Which update may use the terminal's input
+#[derive(Debug, Default)]
+pub(crate) struct StdinLock {
+    holder: Option<UpdateId>,
+    /// Whether the holder is using input, rather than having it reserved while it starts
+    acquired: bool,
+}
+
 #[derive(Debug)]
 pub struct GlobalState {
-    pub(crate) should_try_scheduling: Sender<UpdateId>,
-    pub(crate) has_stdin_lock: Mutex<Option<UpdateId>>,
+    pub(crate) should_try_scheduling: Sender<Notification>,
+    pub(crate) has_stdin_lock: Mutex<StdinLock>,
+    pub(crate) events: Arc<EventBus>,
+}
+
+impl GlobalState {
+    /// The update currently using (or about to use) the terminal's input (if any)
+    pub fn stdin_holder(&self) -> Option<UpdateId> {
+        self.has_stdin_lock.lock().unwrap().holder
+    }
+
+    /// Hold input for an update that is being started, so that no other update that needs input
+    /// is started meanwhile
+    pub(crate) fn reserve_stdin(&self, id: UpdateId) {
+        *self.has_stdin_lock.lock().unwrap() = StdinLock {
+            holder: Some(id),
+            acquired: false,
+        };
+    }
+
+    /// Start using input. Input was already reserved for the update when it was started (see
+    /// [GlobalState::reserve_stdin]) so this doesn't wait, it lets subscribers know input is used.
+    pub(crate) fn acquire_stdin(&self, id: UpdateId) {
+        let mut stdin_lock = self.has_stdin_lock.lock().unwrap();
+        let acquired = stdin_lock.holder == Some(id) && stdin_lock.acquired;
+        *stdin_lock = StdinLock {
+            holder: Some(id),
+            acquired: true,
+        };
+        drop(stdin_lock);
+
+        if !acquired {
+            self.events.emit(EventKind::InputAcquired { id });
+        }
+    }
+
+    /// Release input if the update is holding it, letting the scheduler know that input updates
+    /// can be started
+    pub(crate) fn release_stdin(&self, id: UpdateId) {
+        let mut stdin_lock = self.has_stdin_lock.lock().unwrap();
+        if stdin_lock.holder != Some(id) {
+            return;
+        }
+        let acquired = stdin_lock.acquired;
+        *stdin_lock = StdinLock::default();
+        drop(stdin_lock);
+
+        if acquired {
+            self.events.emit(EventKind::InputReleased { id });
+        }
+        let _ = self
+            .should_try_scheduling
+            .send(Notification::InputReleased(id));
+    }
 }
@@ -0,0 +1,85 @@
+use std::{
+    io::{Read, Write},
+    process::Child,
+    sync::mpsc::Sender,
+    thread::JoinHandle,
+};
+
+use crate::update::{
+    output::{LineSplitter, Stream},
+    Update,
+};
+
+/// Reads a child's piped stdout and stderr as output arrives, adding it to the update's lines and
+/// log file and keeping everything for the final output.
+pub(crate) struct Capture {
+    readers: Vec<JoinHandle<(Stream, Vec<u8>)>>,
+}
+
+impl Capture {
+    /// Start reading, each chunk of output is also sent to `live` (if any)
+    pub(crate) fn start(
+        update: &Update,
+        child: &mut Child,
+        live: Option<Sender<(Stream, Vec<u8>)>>,
+    ) -> Capture {
+        let log = update.open_log();
+
+        let stdout = child
+            .stdout
+            .take()
+            .map(|pipe| (Stream::Stdout, Box::new(pipe) as Box<dyn Read + Send>));
+        let stderr = child
+            .stderr
+            .take()
+            .map(|pipe| (Stream::Stderr, Box::new(pipe) as Box<dyn Read + Send>));
+
+        let readers = stdout
+            .into_iter()
+            .chain(stderr)
+            .map(|(stream, mut pipe)| {
+                let live = live.clone();
+                let sink = update.line_sink();
+                let mut splitter = LineSplitter::new(stream);
+                let mut log = log.as_ref().and_then(|log| log.try_clone().ok());
+
+                std::thread::spawn(move || {
+                    let mut output = Vec::new();
+                    let mut buf = [0; 4096];
+                    while let Ok(n) = pipe.read(&mut buf) {
+                        if n == 0 {
+                            break;
+                        }
+                        output.extend_from_slice(&buf[..n]);
+                        splitter.push(&buf[..n], &sink);
+                        if let Some(log) = log.as_mut() {
+                            let _ = log.write_all(&buf[..n]);
+                        }
+                        if let Some(live) = live.as_ref() {
+                            // Nobody may be listening anymore
+                            let _ = live.send((stream, buf[..n].to_vec()));
+                        }
+                    }
+                    splitter.finish(&sink);
+                    (stream, output)
+                })
+            })
+            .collect();
+
+        Capture { readers }
+    }
+
+    /// Wait for the pipes to close, returning (stdout, stderr)
+    pub(crate) fn finish(self) -> (Vec<u8>, Vec<u8>) {
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        for reader in self.readers {
+            match reader.join() {
+                Ok((Stream::Stdout, output)) => stdout = output,
+                Ok((Stream::Stderr, output)) => stderr = output,
+                Err(_) => {}
+            }
+        }
+
+        (stdout, stderr)
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    collections::HashMap,
+    ffi::CStr,
+    os::unix::process::{CommandExt, ExitStatusExt},
+    path::Path,
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use parallel_update_config::types::When;
+
+use super::{executable, process::ChildProcess, timeout::Watchdog, Update};
+
+/// How long a probe may run for before it is killed
+const PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Read the fields of the os-release file
+fn os_release() -> HashMap<String, String> {
+    let contents = std::fs::read_to_string("/etc/os-release")
+        .or_else(|_| std::fs::read_to_string("/usr/lib/os-release"))
+        .unwrap_or_default();
+
+    contents
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| {
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .or_else(|| {
+                    value
+                        .strip_prefix('\'')
+                        .and_then(|value| value.strip_suffix('\''))
+                })
+                .unwrap_or(value);
+            (key.trim().to_string(), value.to_string())
+        })
+        .collect()
+}
+
+fn hostname() -> Option<String> {
+    let mut buffer = [0u8; 256];
+    // SAFETY: gethostname writes at most buffer.len() bytes to the buffer
+    let result =
+        unsafe { libc::gethostname(buffer.as_mut_ptr() as *mut libc::c_char, buffer.len()) };
+    if result != 0 {
+        return None;
+    }
+
+    CStr::from_bytes_until_nul(&buffer)
+        .ok()
+        .map(|hostname| hostname.to_string_lossy().into_owned())
+}
+
+/// Run a probe, returning why it failed (if it did).
+///
+/// The probe is tracked as the update's child so that it is signalled when the run is cancelled.
+fn probe_failure(update: &Update, probe: &str) -> Option<String> {
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(probe)
+        // Probes must not take input from updates or add to the output
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => return Some(format!("probe {:?} couldn't be run: {}", probe, e)),
+    };
+
+    let process = ChildProcess::group_leader(&child);
+    let watchdog = Watchdog::watch(process, Some(PROBE_TIMEOUT));
+    update.track_process(process);
+    let status = child.wait();
+    update.untrack_child();
+
+    if watchdog.finish() {
+        return Some(format!(
+            "probe {:?} timed out after {}s",
+            probe,
+            PROBE_TIMEOUT.as_secs()
+        ));
+    }
+
+    match status {
+        Ok(status) if status.success() => None,
+        Ok(status) => Some(match (status.code(), status.signal()) {
+            (Some(code), _) => format!("probe {:?} exited with {}", probe, code),
+            (None, Some(signal)) => format!("probe {:?} was killed by signal {}", probe, signal),
+            (None, None) => format!("probe {:?} failed", probe),
+        }),
+        Err(e) => Some(format!("probe {:?} couldn't be waited for: {}", probe, e)),
+    }
+}
+
+/// Check the update's conditions in order, returning the first one that isn't met
+pub(crate) fn unmet(update: &Update) -> Option<String> {
+    if let Some(condition) = unmet_without_probe(&update.info.when) {
+        return Some(condition);
+    }
+
+    if let Some(probe) = update.info.when.probe.as_ref() {
+        return probe_failure(update, probe);
+    }
+
+    None
+}
+
+/// Check the conditions that don't need anything to be run (all but the probe)
+pub(crate) fn unmet_without_probe(when: &When) -> Option<String> {
+    if let Some(file) = when
+        .file_exists
+        .iter()
+        .find(|file| !Path::new(file).exists())
+    {
+        return Some(format!("file {:?} doesn't exist", file));
+    }
+
+    let path = std::env::var_os("PATH");
+    if let Some(name) = when
+        .in_path
+        .iter()
+        .find(|name| executable::find_in_path(name, path.as_deref()).is_none())
+    {
+        return Some(format!("{:?} isn't in PATH", name));
+    }
+
+    if !when.os_release.is_empty() {
+        let fields = os_release();
+        for (key, expected) in &when.os_release {
+            let matches = fields.get(key).is_some_and(|value| {
+                value == expected || value.split_whitespace().any(|word| word == expected)
+            });
+            if !matches {
+                return Some(format!("os-release {} isn't {:?}", key, expected));
+            }
+        }
+    }
+
+    if !when.hostname.is_empty() {
+        let hostname = hostname().unwrap_or_default();
+        if !when.hostname.contains(&hostname) {
+            return Some(format!("hostname {:?} doesn't match", hostname));
+        }
+    }
+
+    if let Some(name) = when
+        .env
+        .iter()
+        .find(|name| std::env::var_os(name).is_none())
+    {
+        return Some(format!("environment variable {} isn't set", name));
+    }
+
+    None
+}
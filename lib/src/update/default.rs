@@ -2,22 +2,23 @@ use std::process::Stdio;
 
 use crate::types::*;
 use crate::update::Update;
-use crate::error::ErrorKind::{CommandOutput, CommandSpawn};
 
-use super::UpdateOutput;
+use super::{capture::Capture, process, pty};
 
 pub fn run(update: &Update, global_state: &GlobalState) {
+    if update.info.pty {
+        pty::run(update, global_state, update.create_command(), None);
+        return;
+    }
+
     if update.info.input {
-        // Must set global stdin lock before we set state to starting
-        // because schedule continues trying to schedule once we have set state to starting.
-        *global_state.has_stdin_lock.lock().unwrap() = Some(update.id);
+        global_state.acquire_stdin(update.id);
     }
 
-    update.state.set(State::Starting);
+    update.set_state(State::Starting);
 
     let mut command = update.create_command();
 
-    // TODO: Capture
     if update.info.input {
         command.stdin(Stdio::inherit());
         command.stdout(Stdio::inherit());
@@ -28,35 +29,22 @@ pub fn run(update: &Update, global_state: &GlobalState) {
         command.stderr(Stdio::piped());
     }
 
-    let child = match command.spawn() {
+    process::configure_command(update, &mut command);
+
+    let mut child = match command.spawn() {
         Ok(child) => child,
         Err(e) => {
-            eprintln!("Error spawning child: {:?}", e);
-            update.state.set(State::Error(CommandSpawn));
+            update.spawn_failed(e);
             return;
         }
     };
-    let start = std::time::Instant::now();
+    let attempt = update.start_attempt(&child);
 
-    update.state.set(State::Running);
-
-    let output = match child.wait_with_output() {
-        Ok(output) => output,
-        Err(e) => {
-            eprintln!("Error waiting for output: {:?}", e);
-            update.state.set(State::Error(CommandOutput));
-            return;
-        }
-    };
-    let duration = start.elapsed();
+    update.set_state(State::Running);
 
-    if output.status.success() {
-        update.state.set(State::Success);
-    } else {
-        update
-            .state
-            .set(State::Failed(output.status.code().unwrap_or(0)));
-    }
+    // Read output as it arrives so that it is logged while the update runs
+    let capture = Capture::start(update, &mut child, None);
+    let (stdout, stderr) = capture.finish();
 
-    *update.output.lock().unwrap() = Some(UpdateOutput { output, duration });
+    update.finish_attempt(&mut child, attempt, stdout, stderr);
 }
@@ -0,0 +1,47 @@
+use std::{
+    ffi::{OsStr, OsString},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use parallel_update_config::types::Program;
+
+/// Whether a path is a file that can be executed
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// Find an executable by name in the directories of a PATH variable
+pub(crate) fn find_in_path(name: &str, path: Option<&OsStr>) -> Option<PathBuf> {
+    std::env::split_paths(path?)
+        .map(|dir| dir.join(name))
+        .find(|path| is_executable(path))
+}
+
+/// Find the executable a program runs the same way it is found when the program is started:
+/// paths are relative to the working directory and names are looked up in the program's PATH
+pub(crate) fn find(program: &Program) -> Option<PathBuf> {
+    if program.exe.contains('/') {
+        let path = match &program.working_directory {
+            Some(working_directory) => Path::new(working_directory).join(&program.exe),
+            None => PathBuf::from(&program.exe),
+        };
+        return is_executable(&path).then_some(path);
+    }
+
+    find_in_path(&program.exe, program_path(program).as_deref())
+}
+
+/// The PATH used to find the program's executable, the program's own PATH if it sets one
+fn program_path(program: &Program) -> Option<OsString> {
+    program
+        .environ
+        .iter()
+        .flatten()
+        .rev()
+        .find(|(key, _)| key == "PATH")
+        .map(|(_, value)| OsString::from(value))
+        .or_else(|| std::env::var_os("PATH"))
+}
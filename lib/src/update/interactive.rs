@@ -0,0 +1,211 @@
+use std::{
+    collections::VecDeque,
+    io::Write,
+    process::{Child, Command, Stdio},
+    sync::mpsc::{channel, Receiver, RecvTimeoutError, TryRecvError},
+    time::Duration,
+};
+
+use crate::error::{context, ErrorKind::InvalidConfig};
+use crate::types::*;
+use crate::update::Update;
+
+use super::{capture::Capture, output::Stream, process, pty, terminal::TerminalMode};
+
+/// How often to check whether to stop waiting for output or an answer
+const ANSWER_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Forward a chunk of an update's output to the user
+fn forward((stream, chunk): &(Stream, Vec<u8>)) {
+    let _ = match stream {
+        Stream::Stdout => std::io::stdout()
+            .write_all(chunk)
+            .and_then(|_| std::io::stdout().flush()),
+        Stream::Stderr => std::io::stderr().write_all(chunk),
+    };
+}
+
+/// Read a line of input to answer a prompt, forwarding output meanwhile (it is also added to
+/// `pending` to be matched against prompts afterwards).
+///
+/// Stops waiting once the child exits or the run is cancelled, returning what was read so far.
+fn read_answer(
+    update: &Update,
+    prompt: &InputPrompt,
+    child: &mut Child,
+    output: &Receiver<(Stream, Vec<u8>)>,
+    pending: &mut VecDeque<(Stream, Vec<u8>)>,
+) -> Vec<u8> {
+    let no_echo = if prompt.secret {
+        TerminalMode::no_echo()
+    } else {
+        None
+    };
+
+    let mut answer = Vec::new();
+    loop {
+        loop {
+            match output.try_recv() {
+                Ok(chunk) => {
+                    forward(&chunk);
+                    pending.push_back(chunk);
+                }
+                Err(TryRecvError::Empty) => break,
+                // The child has closed its output
+                Err(TryRecvError::Disconnected) => return answer,
+            }
+        }
+
+        if update.is_cancelled() || !matches!(child.try_wait(), Ok(None)) {
+            return answer;
+        }
+
+        let mut fds = [libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        }];
+        // SAFETY: fds is a valid array of pollfd
+        let ready =
+            unsafe { libc::poll(fds.as_mut_ptr(), 1, ANSWER_POLL_INTERVAL.as_millis() as _) };
+        if ready <= 0 {
+            // Timed out, or interrupted by a signal
+            continue;
+        }
+
+        // Read a byte at a time so that input after the answer is left for the next prompt
+        let mut byte = 0u8;
+        // SAFETY: byte is valid for one byte
+        let n = unsafe { libc::read(libc::STDIN_FILENO, (&mut byte as *mut u8).cast(), 1) };
+        if n <= 0 {
+            // End of input
+            drop(no_echo);
+            return answer;
+        }
+
+        answer.push(byte);
+        if byte == b'\n' {
+            drop(no_echo);
+            return answer;
+        }
+    }
+}
+
+pub fn run(update: &Update, global_state: &GlobalState) {
+    let command = update.create_command();
+
+    run_with_prompts(update, global_state, command, &update.info.prompts);
+}
+
+/// Run an update forwarding its output, and input for each prompt, until the last prompt has been
+/// answered. Prompts are matched in order but may be skipped (e.g. a password prompt when
+/// credentials are cached).
+pub(crate) fn run_with_prompts(
+    update: &Update,
+    global_state: &GlobalState,
+    mut command: Command,
+    prompts: &[InputPrompt],
+) {
+    if !update.info.input {
+        update.set_error(context!(
+            InvalidConfig,
+            "The interactive runner requires input to be enabled"
+        ));
+        return;
+    }
+
+    if update.info.pty {
+        pty::run(update, global_state, command, Some(prompts));
+        return;
+    }
+
+    global_state.acquire_stdin(update.id);
+
+    update.set_state(State::Starting);
+
+    command.stdin(Stdio::piped());
+    command.stdout(Stdio::piped());
+    command.stderr(Stdio::piped());
+
+    process::configure_command(update, &mut command);
+
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            update.spawn_failed(e);
+            return;
+        }
+    };
+    let attempt = update.start_attempt(&child);
+
+    update.set_state(State::Running);
+
+    // Read both pipes as output arrives, keeping everything for the final output
+    let (tx, rx) = channel();
+    let capture = Capture::start(update, &mut child, Some(tx));
+
+    {
+        let mut child_stdin = child.stdin.take();
+        let mut next_prompt = 0;
+        // Current (partial) line of each stream and whether it has been answered
+        let mut lines = [String::new(), String::new()];
+        let mut answered = [false, false];
+        // Output that arrived (and was forwarded) while waiting for an answer
+        let mut pending = VecDeque::new();
+
+        'output: while next_prompt < prompts.len() {
+            let (stream, chunk) = match pending.pop_front() {
+                Some(chunk) => chunk,
+                None => match rx.recv_timeout(ANSWER_POLL_INTERVAL) {
+                    Ok(chunk) => {
+                        forward(&chunk);
+                        chunk
+                    }
+                    // Stop forwarding input once cancelled, closing the child's input
+                    Err(RecvTimeoutError::Timeout) if !update.is_cancelled() => continue,
+                    Err(_) => break,
+                },
+            };
+
+            let i = stream as usize;
+            for segment in String::from_utf8_lossy(&chunk).split_inclusive('\n') {
+                lines[i].push_str(segment.trim_end_matches(['\r', '\n']));
+
+                if !answered[i] {
+                    if let Some(found) = prompts[next_prompt..]
+                        .iter()
+                        .position(|prompt| prompt.matches(&lines[i]))
+                    {
+                        answered[i] = true;
+                        let prompt = &prompts[next_prompt + found];
+                        next_prompt += found + 1;
+
+                        let answer = read_answer(update, prompt, &mut child, &rx, &mut pending);
+                        if let Some(child_stdin) = child_stdin.as_mut() {
+                            let _ = child_stdin.write_all(&answer);
+                        }
+
+                        if update.is_cancelled() || next_prompt == prompts.len() {
+                            break 'output;
+                        }
+                    }
+                }
+
+                if segment.ends_with('\n') {
+                    lines[i].clear();
+                    answered[i] = false;
+                }
+            }
+        }
+    }
+
+    // Stop buffering output nobody will forward
+    drop(rx);
+
+    // We no-longer need stdin
+    global_state.release_stdin(update.id);
+
+    let (stdout, stderr) = capture.finish();
+
+    update.finish_attempt(&mut child, attempt, stdout, stderr);
+}
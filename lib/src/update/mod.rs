@@ -1,33 +1,86 @@
 use std::{
     collections::HashMap,
-    process::{Command, Output},
-    sync::Mutex,
-    time::Duration,
+    fs::File,
+    os::unix::process::ExitStatusExt,
+    path::PathBuf,
+    process::{Child, Command, Output},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
 };
 
-use parallel_update_config::{config::UpdateConfig, primatives::UpdateKind, types::Program};
+use parallel_update_config::{
+    config::UpdateConfig,
+    primatives::{IfMissing, UpdateKind},
+    types::Program,
+};
 
-use crate::error::{context, ErrorKind::InvalidConfig, Result};
+use crate::error::{
+    context, Error,
+    ErrorKind::{CommandOutput, CommandSpawn, InvalidConfig},
+    Result,
+};
+use crate::events::{EventBus, EventKind};
 use crate::types::*;
 
+mod capture;
+mod condition;
 pub mod default;
+mod executable;
+pub mod interactive;
+pub mod output;
 pub mod paru;
+mod process;
+mod pty;
+mod terminal;
+mod timeout;
+
+use output::{LineSink, OutputBuffer};
+use process::ChildProcess;
+use timeout::Watchdog;
 
 pub type UpdateRunner = &'static (dyn Fn(&Update, &GlobalState) + Sync);
 
 #[derive(Debug, Clone)]
 pub struct UpdateOutput {
     pub output: Output,
+    /// When the attempt was started
+    pub started: SystemTime,
     pub duration: Duration,
 }
 
+/// An attempt at running an update whose child has been started
+pub(crate) struct Attempt {
+    started: SystemTime,
+    start: Instant,
+    watchdog: Watchdog,
+}
+
 /// An Update that can be run.
 pub struct Update {
     pub id: UpdateId,
+    /// The name of the update (key in the config)
+    pub name: String,
     pub info: Info,
     pub state: SyncState,
     pub program: Program,
-    pub output: Mutex<Option<UpdateOutput>>,
+    /// Output of each attempt at running the update
+    pub outputs: Mutex<Vec<UpdateOutput>>,
+    /// Why the update couldn't be run (when in [State::Error])
+    pub(crate) error: Mutex<Option<Arc<Error>>>,
+    /// Output lines of every attempt as they are read
+    pub lines: Arc<OutputBuffer>,
+    pub(crate) attempts: AtomicUsize,
+    /// Set when the run is cancelled while the update is waiting to be retried
+    retries_stopped: AtomicBool,
+    /// Set when the run is cancelled, so that the update stops waiting for input
+    cancelled: AtomicBool,
+    pub(crate) child: Mutex<Option<ChildProcess>>,
+    /// File that output is written to as the update runs
+    pub(crate) log_path: Mutex<Option<PathBuf>>,
+    pub(crate) events: Arc<EventBus>,
     pub(crate) run: UpdateRunner,
 }
 
@@ -35,35 +88,293 @@ impl std::fmt::Debug for Update {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Update")
             .field("id", &self.id)
+            .field("name", &self.name)
             .field("info", &self.info)
             .field("state", &self.state)
             .field("program", &self.program)
-            .field("output", &self.output)
+            .field("outputs", &self.outputs)
+            .field("attempts", &self.attempts)
             .finish()
     }
 }
 
 impl Update {
-    pub fn new(id: UpdateId, program: Program, info: Info) -> Self {
-        Update::new_with_runnner(id, program, info, &default::run)
+    pub fn new(id: UpdateId, name: impl Into<String>, program: Program, info: Info) -> Self {
+        Update::new_with_runnner(id, name, program, info, &default::run)
     }
 
     pub fn new_with_runnner(
         id: UpdateId,
+        name: impl Into<String>,
         program: Program,
         info: Info,
         runner: UpdateRunner,
     ) -> Self {
         Update {
             id,
+            name: name.into(),
             program,
             info,
             state: SyncState::new(State::Pending),
-            output: Mutex::new(None),
+            outputs: Mutex::new(Vec::new()),
+            error: Mutex::new(None),
+            lines: Arc::new(OutputBuffer::new()),
+            attempts: AtomicUsize::new(0),
+            retries_stopped: AtomicBool::new(false),
+            cancelled: AtomicBool::new(false),
+            child: Mutex::new(None),
+            log_path: Mutex::new(None),
+            events: Arc::new(EventBus::default()),
             run: runner,
         }
     }
 
+    /// Change the state, letting subscribers know
+    pub(crate) fn set_state(&self, state: State) {
+        let from = self.state.set(state.clone());
+        if from != state {
+            self.events.emit(EventKind::StateChanged {
+                id: self.id,
+                from,
+                to: state.clone(),
+            });
+
+            if self.is_finished() {
+                self.events
+                    .emit(EventKind::UpdateFinished { id: self.id, state });
+            }
+        }
+    }
+
+    /// Keep the error that stopped the update and set the state to [State::Error]
+    pub(crate) fn set_error(&self, error: Error) {
+        let kind = error.kind();
+        // Store the error before setting state so that it is available once the update is done
+        *self.error.lock().unwrap() = Some(Arc::new(error));
+        self.set_state(State::Error(kind));
+    }
+
+    /// The error that stopped the update (if any)
+    pub fn error(&self) -> Option<Arc<Error>> {
+        self.error.lock().unwrap().clone()
+    }
+
+    /// Why the update wouldn't be run (if it wouldn't be), a missing executable only skips the
+    /// update if `if_missing` is `Skip`, otherwise the update fails
+    fn skip_reason(&self, run_probe: bool) -> Option<SkipReason> {
+        let condition = if run_probe {
+            condition::unmet(self)
+        } else {
+            condition::unmet_without_probe(&self.info.when)
+        };
+        if let Some(condition) = condition {
+            return Some(SkipReason::Condition(condition));
+        }
+
+        if executable::find(&self.program).is_none() {
+            return Some(SkipReason::MissingExecutable);
+        }
+
+        None
+    }
+
+    /// The state the update would finish in without an attempt (if it wouldn't be run), found
+    /// without running anything so the probe isn't checked
+    pub fn expected_state(&self) -> Option<State> {
+        match self.skip_reason(false)? {
+            SkipReason::MissingExecutable if self.info.if_missing == IfMissing::Error => {
+                Some(State::Error(CommandSpawn))
+            }
+            reason => Some(State::Skipped(reason)),
+        }
+    }
+
+    /// Check that the update should and can be run, setting the state it finishes in if not
+    pub(crate) fn check_runnable(&self) -> bool {
+        match self.skip_reason(true) {
+            None => true,
+            Some(SkipReason::MissingExecutable) if self.info.if_missing == IfMissing::Error => {
+                self.set_error(context!(
+                    CommandSpawn,
+                    "Executable {:?} wasn't found",
+                    self.program.exe
+                ));
+                false
+            }
+            Some(reason) => {
+                self.set_state(State::Skipped(reason));
+                false
+            }
+        }
+    }
+
+    pub(crate) fn line_sink(&self) -> LineSink {
+        LineSink {
+            id: self.id,
+            buffer: self.lines.clone(),
+            events: self.events.clone(),
+        }
+    }
+
+    /// Record that the child of an attempt couldn't be started
+    pub(crate) fn spawn_failed(&self, error: std::io::Error) {
+        self.set_error(
+            CommandSpawn
+                .context_with_source(format!("Unable to start {:?}", self.program.exe), error),
+        );
+    }
+
+    /// Start timing an attempt once its child has been started, keeping track of the child so
+    /// that it can be signalled (and killed if it times out)
+    pub(crate) fn start_attempt(&self, child: &Child) -> Attempt {
+        let attempt = Attempt {
+            started: SystemTime::now(),
+            start: Instant::now(),
+            watchdog: Watchdog::start(self, child),
+        };
+        self.track_child(child);
+
+        attempt
+    }
+
+    /// Wait for the child of an attempt to exit, then store its output and set the state it
+    /// finished in
+    pub(crate) fn finish_attempt(
+        &self,
+        child: &mut Child,
+        attempt: Attempt,
+        stdout: Vec<u8>,
+        stderr: Vec<u8>,
+    ) {
+        let status = child.wait();
+        let duration = attempt.start.elapsed();
+        self.untrack_child();
+        let timed_out = attempt.watchdog.finish();
+
+        let status =
+            match status {
+                Ok(status) => status,
+                Err(e) => {
+                    self.set_error(CommandOutput.context_with_source(
+                        format!("Unable to wait for {:?}", self.program.exe),
+                        e,
+                    ));
+                    return;
+                }
+            };
+
+        let state = if timed_out {
+            State::TimedOut
+        } else if status.success() {
+            State::Success
+        } else if let Some(signal) = status.signal() {
+            State::Killed(signal)
+        } else {
+            State::Failed(status.code().unwrap_or(0))
+        };
+
+        // Store output before setting state so that it is available once the update is done
+        self.outputs.lock().unwrap().push(UpdateOutput {
+            output: Output {
+                status,
+                stdout,
+                stderr,
+            },
+            started: attempt.started,
+            duration,
+        });
+        self.set_state(state);
+    }
+
+    /// Output of the most recent attempt
+    pub fn output(&self) -> Option<UpdateOutput> {
+        self.outputs.lock().unwrap().last().cloned()
+    }
+
+    /// Number of times the update has been started
+    pub fn attempts(&self) -> usize {
+        self.attempts.load(Ordering::SeqCst)
+    }
+
+    /// Whether the update failed but has attempts remaining
+    pub fn will_retry(&self) -> bool {
+        self.state.get().is_retryable()
+            && self.attempts() <= self.info.retries
+            && !self.retries_stopped.load(Ordering::SeqCst)
+    }
+
+    /// Whether the update is done and won't be retried
+    pub fn is_finished(&self) -> bool {
+        self.state.get().is_done() && !self.will_retry()
+    }
+
+    /// How long to wait before the next attempt
+    fn retry_delay(&self) -> Duration {
+        if self.info.retry_backoff {
+            let doublings = self.attempts().saturating_sub(1).min(16) as u32;
+            self.info.retry_delay * 2u32.pow(doublings)
+        } else {
+            self.info.retry_delay
+        }
+    }
+
+    /// When a failed update should be retried, it keeps its failed state until then
+    pub(crate) fn retry_at(&self) -> Instant {
+        Instant::now() + self.retry_delay()
+    }
+
+    /// Don't retry a failed update, leaving it in the state its last attempt finished in
+    pub(crate) fn stop_retrying(&self) {
+        self.retries_stopped.store(true, Ordering::SeqCst);
+        self.events.emit(EventKind::UpdateFinished {
+            id: self.id,
+            state: self.state.get(),
+        });
+    }
+
+    /// The file output is logged to (if any)
+    pub fn log_path(&self) -> Option<PathBuf> {
+        self.log_path.lock().unwrap().clone()
+    }
+
+    /// Open the log file for an attempt
+    pub(crate) fn open_log(&self) -> Option<File> {
+        self.log_path().and_then(|path| crate::logs::open(&path))
+    }
+
+    /// Keep track of the running child so that it can be signalled
+    pub(crate) fn track_child(&self, child: &std::process::Child) {
+        self.track_process(ChildProcess::new(self, child));
+    }
+
+    pub(crate) fn track_process(&self, process: ChildProcess) {
+        *self.child.lock().unwrap() = Some(process);
+    }
+
+    pub(crate) fn untrack_child(&self) {
+        *self.child.lock().unwrap() = None;
+    }
+
+    /// Stop the update because the run was cancelled by a signal, which is forwarded to the
+    /// running child (if any)
+    pub(crate) fn cancel(&self, signal: i32) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        if let Some(child) = *self.child.lock().unwrap() {
+            child.forward(signal);
+        }
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Send a signal to the running child (if any)
+    pub(crate) fn signal(&self, signal: i32) {
+        if let Some(child) = *self.child.lock().unwrap() {
+            child.signal(signal);
+        }
+    }
+
     fn create_command(&self) -> Command {
         let mut command = Command::new(&self.program.exe);
 
@@ -94,33 +405,117 @@ impl Update {
         let mut conflicts = Vec::with_capacity(config.conflicts.len());
         for conflict in config.conflicts.into_iter() {
             conflicts.push(*id_map.get(&conflict).ok_or_else(|| {
-                context!(InvalidConfig, "Conflict doesn't exist: {:?}", conflict)
+                context!(
+                    InvalidConfig,
+                    "Conflict of {:?} doesn't exist: {:?}",
+                    name,
+                    conflict
+                )
             })?);
         }
 
         let mut depends = Vec::with_capacity(config.depends.len());
         for depend in config.depends.into_iter() {
             depends.push(*id_map.get(&depend).ok_or_else(|| {
-                context!(InvalidConfig, "Dependency doesn't exist: {:?}", depend)
+                context!(
+                    InvalidConfig,
+                    "Dependency of {:?} doesn't exist: {:?}",
+                    name,
+                    depend
+                )
             })?);
         }
 
+        match config.kind {
+            UpdateKind::Paru | UpdateKind::Interactive if !config.input => {
+                return Err(context!(
+                    InvalidConfig,
+                    "Update {:?} forwards input for prompts so it needs input to be enabled",
+                    name
+                ));
+            }
+            UpdateKind::Default | UpdateKind::Paru if !config.prompts.is_empty() => {
+                return Err(context!(
+                    InvalidConfig,
+                    "Update {:?} has prompts but only interactive updates use them",
+                    name
+                ));
+            }
+            _ => {}
+        }
+
+        // Updates that use input stay in the terminal's process group so a timeout could only kill
+        // the child itself and not everything it started
+        if config.timeout.is_some() && config.input && !config.pty {
+            return Err(context!(
+                InvalidConfig,
+                "Update {:?} can't have a timeout and use input without a pseudo-terminal",
+                name
+            ));
+        }
+
+        let prompts = config
+            .prompts
+            .into_iter()
+            .map(InputPrompt::try_from)
+            .collect::<Result<_>>()?;
+
         Ok(Update::new_with_runnner(
             *id_map
                 .get(name)
                 .ok_or_else(|| context!(InvalidConfig, "Name doesn't exist: {:?}", name))?,
+            name,
             config.program,
             Info {
                 input: config.input,
                 root: config.root,
+                prompts,
+                pty: config.pty,
                 parallel: true,
+                priority: config.priority,
+                timeout: config.timeout.map(Duration::from_secs),
+                retries: config.retries,
+                retry_delay: Duration::from_secs(config.retry_delay),
+                retry_backoff: config.retry_backoff,
+                if_missing: config.if_missing,
+                when: config.when,
                 conflicts,
                 depends,
             },
             match config.kind {
                 UpdateKind::Default => &default::run,
                 UpdateKind::Paru => &paru::run,
+                UpdateKind::Interactive => &interactive::run,
             },
         ))
     }
 }
+
+#[cfg(test)]
+impl Update {
+    /// An update that does nothing with the given dependencies and conflicts
+    pub(crate) fn for_test(id: usize, depends: &[usize], conflicts: &[usize]) -> Update {
+        Update::new_with_runnner(
+            UpdateId(id),
+            format!("update{}", id),
+            Program::new("true"),
+            Info {
+                input: false,
+                root: false,
+                prompts: Vec::new(),
+                pty: false,
+                parallel: true,
+                priority: 0,
+                timeout: None,
+                retries: 0,
+                retry_delay: Duration::ZERO,
+                retry_backoff: false,
+                if_missing: IfMissing::Error,
+                when: Default::default(),
+                conflicts: conflicts.iter().copied().map(UpdateId).collect(),
+                depends: depends.iter().copied().map(UpdateId).collect(),
+            },
+            &|_, _| {},
+        )
+    }
+}
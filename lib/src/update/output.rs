@@ -0,0 +1,125 @@
+use std::{
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    time::SystemTime,
+};
+
+use crate::{
+    events::{EventBus, EventKind},
+    types::UpdateId,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stream {
+    Stdout = 0,
+    Stderr = 1,
+}
+
+/// A line output by an update
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputLine {
+    /// When the end of the line was read
+    pub time: SystemTime,
+    pub stream: Stream,
+    /// The line without its line ending
+    pub text: String,
+}
+
+/// Output of an update (across all attempts) as lines in the order they were read.
+///
+/// Unlike [`UpdateOutput`](super::UpdateOutput), which is only available once an attempt is done,
+/// lines are added while the update runs and can be followed with [`OutputBuffer::subscribe`].
+#[derive(Debug, Default)]
+pub struct OutputBuffer {
+    lines: Mutex<Vec<OutputLine>>,
+    subscribers: Mutex<Vec<Sender<OutputLine>>>,
+}
+
+impl OutputBuffer {
+    pub fn new() -> OutputBuffer {
+        OutputBuffer::default()
+    }
+
+    /// All lines read so far
+    pub fn lines(&self) -> Vec<OutputLine> {
+        self.lines.lock().unwrap().clone()
+    }
+
+    /// Receive each line read from now on, the channel is disconnected once the buffer is dropped
+    pub fn subscribe(&self) -> Receiver<OutputLine> {
+        let (tx, rx) = channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    pub(crate) fn push(&self, line: OutputLine) {
+        // Forget subscribers that have stopped listening
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|subscriber| subscriber.send(line.clone()).is_ok());
+        self.lines.lock().unwrap().push(line);
+    }
+}
+
+/// Where an update's output lines go: its buffer and the updater's events
+#[derive(Debug, Clone)]
+pub(crate) struct LineSink {
+    pub(crate) id: UpdateId,
+    pub(crate) buffer: Arc<OutputBuffer>,
+    pub(crate) events: Arc<EventBus>,
+}
+
+impl LineSink {
+    fn push(&self, line: OutputLine) {
+        self.buffer.push(line.clone());
+        self.events.emit(EventKind::Output { id: self.id, line });
+    }
+}
+
+/// Splits chunks of a stream into lines
+pub(crate) struct LineSplitter {
+    stream: Stream,
+    partial: Vec<u8>,
+}
+
+impl LineSplitter {
+    pub(crate) fn new(stream: Stream) -> LineSplitter {
+        LineSplitter {
+            stream,
+            partial: Vec::new(),
+        }
+    }
+
+    /// Output each complete line in the chunk, keeping the rest for the next chunk
+    pub(crate) fn push(&mut self, chunk: &[u8], sink: &LineSink) {
+        self.partial.extend_from_slice(chunk);
+
+        while let Some(end) = self.partial.iter().position(|c| *c == b'\n') {
+            let line: Vec<u8> = self.partial.drain(..=end).collect();
+            self.emit(&line, sink);
+        }
+    }
+
+    /// Output whatever is left once the stream is closed
+    pub(crate) fn finish(mut self, sink: &LineSink) {
+        if !self.partial.is_empty() {
+            let line = std::mem::take(&mut self.partial);
+            self.emit(&line, sink);
+        }
+    }
+
+    fn emit(&self, line: &[u8], sink: &LineSink) {
+        let text = String::from_utf8_lossy(line)
+            .trim_end_matches(['\r', '\n'])
+            .to_string();
+
+        sink.push(OutputLine {
+            time: SystemTime::now(),
+            stream: self.stream,
+            text,
+        });
+    }
+}
@@ -0,0 +1,66 @@
+use std::{
+    os::unix::process::CommandExt,
+    process::{Child, Command},
+};
+
+use crate::update::Update;
+
+/// A child process started by an update.
+///
+/// Updates with a timeout that don't use input are started in their own process group so that
+/// the whole group can be signalled, updates that use input have to stay in the terminal's
+/// foreground process group so only the child itself is signalled (which is why a timeout with
+/// input is rejected in the config). Updates in a pseudo-terminal have their own session (and
+/// process group).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ChildProcess {
+    pid: libc::pid_t,
+    own_group: bool,
+}
+
+fn uses_own_group(update: &Update) -> bool {
+    update.info.pty || (update.info.timeout.is_some() && !update.info.input)
+}
+
+/// Put the command in its own process group if the update can be signalled as a group
+pub(crate) fn configure_command(update: &Update, command: &mut Command) {
+    if uses_own_group(update) && !update.info.pty {
+        command.process_group(0);
+    }
+}
+
+impl ChildProcess {
+    pub(crate) fn new(update: &Update, child: &Child) -> ChildProcess {
+        ChildProcess {
+            pid: child.id() as libc::pid_t,
+            own_group: uses_own_group(update),
+        }
+    }
+
+    /// A child that was started in its own process group (with `process_group(0)`)
+    pub(crate) fn group_leader(child: &Child) -> ChildProcess {
+        ChildProcess {
+            pid: child.id() as libc::pid_t,
+            own_group: true,
+        }
+    }
+
+    /// Forward a signal the updater received. Ctrl-C already sends SIGINT to the terminal's
+    /// foreground process group, so only children in their own group are sent it again.
+    pub(crate) fn forward(&self, signal: libc::c_int) {
+        if signal != libc::SIGINT || self.own_group {
+            self.signal(signal);
+        }
+    }
+
+    pub(crate) fn signal(&self, signal: libc::c_int) {
+        // SAFETY: kill has no memory safety requirements
+        unsafe {
+            if self.own_group {
+                libc::killpg(self.pid, signal);
+            } else {
+                libc::kill(self.pid, signal);
+            }
+        }
+    }
+}
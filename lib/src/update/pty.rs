@@ -0,0 +1,246 @@
+use std::{
+    fs::File,
+    io::{Error, Read, Write},
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::process::CommandExt,
+    },
+    process::{Command, Stdio},
+};
+
+use crate::error::ErrorKind::CommandSpawn;
+use crate::types::*;
+use crate::update::Update;
+
+use super::{
+    output::{LineSplitter, Stream},
+    terminal::TerminalMode,
+};
+
+/// A pseudo-terminal owned by the updater
+struct Pty {
+    master: File,
+    slave: OwnedFd,
+}
+
+impl Pty {
+    /// Open a pseudo-terminal the same size as the user's terminal
+    fn open() -> std::io::Result<Pty> {
+        let mut master = -1;
+        let mut slave = -1;
+
+        // SAFETY: all pointers are valid for the duration of the calls, and the returned file
+        // descriptors are owned by nothing else
+        unsafe {
+            let mut size: libc::winsize = std::mem::zeroed();
+            let size = if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) == 0 {
+                &size as *const libc::winsize
+            } else {
+                std::ptr::null()
+            };
+
+            if libc::openpty(
+                &mut master,
+                &mut slave,
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                size,
+            ) != 0
+            {
+                return Err(Error::last_os_error());
+            }
+
+            libc::fcntl(master, libc::F_SETFD, libc::FD_CLOEXEC);
+            libc::fcntl(slave, libc::F_SETFD, libc::FD_CLOEXEC);
+
+            Ok(Pty {
+                master: File::from_raw_fd(master),
+                slave: OwnedFd::from_raw_fd(slave),
+            })
+        }
+    }
+
+    /// Use the pseudo-terminal as the command's controlling terminal and stdio
+    fn configure_command(&self, command: &mut Command) -> std::io::Result<()> {
+        command.stdin(Stdio::from(self.slave.try_clone()?));
+        command.stdout(Stdio::from(self.slave.try_clone()?));
+        command.stderr(Stdio::from(self.slave.try_clone()?));
+
+        // SAFETY: only async-signal-safe functions are called
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                    return Err(Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Run an update in a pseudo-terminal, relaying it to the user's terminal while the update holds
+/// input and capturing everything it outputs.
+///
+/// With `prompts` input is released once the last prompt has been answered, otherwise it is held
+/// until the update exits.
+pub(crate) fn run(
+    update: &Update,
+    global_state: &GlobalState,
+    mut command: Command,
+    prompts: Option<&[InputPrompt]>,
+) {
+    if update.info.input {
+        global_state.acquire_stdin(update.id);
+    }
+
+    update.set_state(State::Starting);
+
+    let pty = match Pty::open() {
+        Ok(pty) => pty,
+        Err(e) => {
+            update
+                .set_error(CommandSpawn.context_with_source("Unable to open a pseudo-terminal", e));
+            return;
+        }
+    };
+
+    let child = pty
+        .configure_command(&mut command)
+        .and_then(|_| command.spawn());
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            update.spawn_failed(e);
+            return;
+        }
+    };
+    let attempt = update.start_attempt(&child);
+
+    // Only the child should hold the terminal open, so we see when it exits
+    drop(command);
+    let Pty { mut master, slave } = pty;
+    drop(slave);
+
+    update.set_state(State::Running);
+
+    let mut transcript = Vec::new();
+    let mut log = update.open_log();
+    // The terminal combines stdout and stderr
+    let mut splitter = LineSplitter::new(Stream::Stdout);
+    let sink = update.line_sink();
+    {
+        let release_input = || global_state.release_stdin(update.id);
+
+        let mut relaying = update.info.input;
+        let mut stdin_open = true;
+        let mut raw_mode = if relaying { TerminalMode::raw() } else { None };
+
+        let mut next_prompt = 0;
+        let mut line = String::new();
+        let mut answered = false;
+        // The last prompt has been seen so input is released after the next line of input
+        let mut last_prompt = false;
+
+        if relaying && prompts.is_some_and(|prompts| prompts.is_empty()) {
+            relaying = false;
+            raw_mode = None;
+            release_input();
+        }
+
+        let mut buf = [0; 4096];
+        loop {
+            let mut fds = [
+                libc::pollfd {
+                    fd: master.as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                },
+                // Negative file descriptors are ignored
+                libc::pollfd {
+                    fd: if relaying && stdin_open {
+                        libc::STDIN_FILENO
+                    } else {
+                        -1
+                    },
+                    events: libc::POLLIN,
+                    revents: 0,
+                },
+            ];
+
+            // SAFETY: fds is a valid array of pollfd
+            if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as _, -1) } < 0 {
+                if Error::last_os_error().kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                break;
+            }
+
+            if fds[0].revents != 0 {
+                // Reading fails once the child has closed the terminal
+                let n = match master.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => n,
+                };
+                transcript.extend_from_slice(&buf[..n]);
+                splitter.push(&buf[..n], &sink);
+                if let Some(log) = log.as_mut() {
+                    let _ = log.write_all(&buf[..n]);
+                }
+
+                if relaying {
+                    let mut stdout = std::io::stdout();
+                    let _ = stdout.write_all(&buf[..n]).and_then(|_| stdout.flush());
+                }
+
+                if let Some(prompts) = prompts.filter(|_| relaying && !last_prompt) {
+                    for segment in String::from_utf8_lossy(&buf[..n]).split_inclusive('\n') {
+                        line.push_str(segment.trim_end_matches(['\r', '\n']));
+
+                        if !answered {
+                            if let Some(found) = prompts[next_prompt..]
+                                .iter()
+                                .position(|prompt| prompt.matches(&line))
+                            {
+                                answered = true;
+                                next_prompt += found + 1;
+                                last_prompt = next_prompt == prompts.len();
+                            }
+                        }
+
+                        if segment.ends_with('\n') {
+                            line.clear();
+                            answered = false;
+                        }
+                    }
+                }
+            }
+
+            if fds[1].revents != 0 {
+                // SAFETY: buf is valid for its length
+                let n =
+                    unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) };
+                if n <= 0 {
+                    // Pass on the end of input (Ctrl-D)
+                    stdin_open = false;
+                    let _ = master.write_all(&[4]);
+                    continue;
+                }
+                let input = &buf[..n as usize];
+                let _ = master.write_all(input);
+
+                if last_prompt && input.iter().any(|c| *c == b'\r' || *c == b'\n') {
+                    relaying = false;
+                    raw_mode = None;
+                    release_input();
+                }
+            }
+        }
+
+        drop(raw_mode);
+    }
+    splitter.finish(&sink);
+
+    update.finish_attempt(&mut child, attempt, transcript, Vec::new());
+}
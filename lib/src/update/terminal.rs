@@ -0,0 +1,55 @@
+/// Changes the settings of the user's terminal, restoring them when dropped
+pub(crate) struct TerminalMode {
+    original: libc::termios,
+}
+
+impl TerminalMode {
+    /// Change the settings of the terminal on stdin, if stdin is a terminal
+    fn set(change: impl FnOnce(&mut libc::termios)) -> Option<TerminalMode> {
+        // SAFETY: termios is only accessed through valid pointers
+        unsafe {
+            if libc::isatty(libc::STDIN_FILENO) != 1 {
+                return None;
+            }
+
+            let mut original: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+                return None;
+            }
+
+            let mut changed = original;
+            change(&mut changed);
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &changed);
+
+            Some(TerminalMode { original })
+        }
+    }
+
+    /// Pass keys straight through
+    pub(crate) fn raw() -> Option<TerminalMode> {
+        TerminalMode::set(|termios| {
+            // SAFETY: termios is a valid termios
+            unsafe { libc::cfmakeraw(termios) };
+            // Keep output processing for anything else that is printed meanwhile
+            termios.c_oflag |= libc::OPOST;
+        })
+    }
+
+    /// Don't echo input, so that answers (e.g. passwords) aren't shown as they are typed
+    pub(crate) fn no_echo() -> Option<TerminalMode> {
+        TerminalMode::set(|termios| {
+            termios.c_lflag &= !libc::ECHO;
+            // Still move to the next line once the answer is entered
+            termios.c_lflag |= libc::ECHONL;
+        })
+    }
+}
+
+impl Drop for TerminalMode {
+    fn drop(&mut self) {
+        // SAFETY: original is a valid termios
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::{
+    process::Child,
+    sync::mpsc::{channel, RecvTimeoutError, Sender},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use crate::update::Update;
+
+use super::process::ChildProcess;
+
+/// Kills an update's child if it runs for longer than the update's timeout.
+pub(crate) struct Watchdog {
+    cancel: Sender<()>,
+    handle: Option<JoinHandle<bool>>,
+}
+
+impl Watchdog {
+    pub(crate) fn start(update: &Update, child: &Child) -> Watchdog {
+        Watchdog::watch(ChildProcess::new(update, child), update.info.timeout)
+    }
+
+    /// Kill a process if it runs for longer than the timeout (if any)
+    pub(crate) fn watch(process: ChildProcess, timeout: Option<Duration>) -> Watchdog {
+        let (cancel, cancelled) = channel();
+
+        let handle = timeout.map(|timeout| {
+            std::thread::spawn(move || {
+                if cancelled.recv_timeout(timeout) != Err(RecvTimeoutError::Timeout) {
+                    return false;
+                }
+
+                process.signal(libc::SIGKILL);
+
+                true
+            })
+        });
+
+        Watchdog { cancel, handle }
+    }
+
+    /// Stop watching the child, returns whether it was killed for timing out
+    pub(crate) fn finish(self) -> bool {
+        let _ = self.cancel.send(());
+
+        self.handle
+            .map(|handle| handle.join().unwrap_or(false))
+            .unwrap_or(false)
+    }
+}
@@ -1,171 +1,524 @@
 use std::{
+    any::Any,
+    cmp::Reverse,
     collections::{HashMap, HashSet},
-    sync::{mpsc::channel, Arc, Mutex},
+    panic::AssertUnwindSafe,
+    sync::{
+        atomic::Ordering,
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
 use parallel_update_config::config::{Config, UpdaterConfig};
 
 use crate::{
     error::{bail, ErrorKind::InvalidUpdater, Result},
+    events::{Event, EventBus, EventKind},
+    hooks::Hooks,
+    scheduler::{ScheduleKey, Scheduler},
     types::*,
-    Update,
+    History, RunLogs, Update,
 };
 
+/// The message a panic was started with
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Unknown panic".to_string()
+    }
+}
+
+/// Estimated duration of updates that have never run before
+const DEFAULT_DURATION: Duration = Duration::from_secs(1);
+
+/// How updates would be run, see [Updater::plan]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan {
+    /// Updates that would be started together, an update only starts in a wave after all its
+    /// dependencies
+    pub waves: Vec<Vec<UpdateId>>,
+    /// Updates that wouldn't run because an update they depend on would fail
+    pub ignored: Vec<UpdateId>,
+}
+
 #[derive(Debug)]
 pub struct Updater {
     updates: Vec<Arc<Update>>,
+    /// Estimated time from starting each update until all its dependents are done
+    critical_path: Vec<Duration>,
+    global_state: Arc<GlobalState>,
+    notifications: Receiver<Notification>,
+    hooks: Hooks,
+}
+
+/// Cancels a running [Updater] from another thread
+#[derive(Debug, Clone)]
+pub struct CancelHandle {
+    should_try_scheduling: Sender<Notification>,
+}
+
+impl CancelHandle {
+    /// Stop starting new updates and forward the signal to running updates
+    pub fn cancel(&self, signal: i32) {
+        let _ = self
+            .should_try_scheduling
+            .send(Notification::Cancel(signal));
+    }
 }
 
 fn validate_updates(updates: &[Update]) -> Result<()> {
     for update in updates {
         if update.id.0 >= updates.len() {
-            bail!(InvalidUpdater, "Update ID is out of bounds: {:?}", update);
+            bail!(
+                InvalidUpdater,
+                "ID of {:?} is out of bounds: {}",
+                update.name,
+                update.id.0
+            );
         }
         for dependency in &update.info.depends {
             if dependency.0 >= updates.len() {
                 bail!(
                     InvalidUpdater,
-                    "Dependency ID is out of bounds: {:?}",
-                    update
+                    "Dependency ID of {:?} is out of bounds: {}",
+                    update.name,
+                    dependency.0
                 );
             }
             if *dependency == update.id {
                 bail!(
                     InvalidUpdater,
                     "Update cannot depend on itself: {:?}",
-                    update
+                    update.name
                 );
             }
         }
         for conflict in &update.info.conflicts {
             if conflict.0 >= updates.len() {
-                bail!(InvalidUpdater, "Conflict ID is out of bounds: {:?}", update);
+                bail!(
+                    InvalidUpdater,
+                    "Conflict ID of {:?} is out of bounds: {}",
+                    update.name,
+                    conflict.0
+                );
             }
             if *conflict == update.id {
                 bail!(
                     InvalidUpdater,
                     "Update cannot conflict with itself: {:?}",
-                    update
+                    update.name
                 );
             }
         }
     }
 
+    if let Some(cycle) = find_dependency_cycle(updates) {
+        let names: Vec<_> = cycle.iter().map(|id| updates[id.0].name.as_str()).collect();
+        bail!(InvalidUpdater, "Dependency cycle: {}", names.join(" -> "));
+    }
+
     Ok(())
 }
 
+/// Find a cycle in the dependency graph, the returned path starts and ends with the same update.
+fn find_dependency_cycle(updates: &[Update]) -> Option<Vec<UpdateId>> {
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    enum Mark {
+        Unvisited,
+        Visiting,
+        Visited,
+    }
+
+    fn visit(
+        updates: &[Update],
+        id: UpdateId,
+        marks: &mut [Mark],
+        path: &mut Vec<UpdateId>,
+    ) -> bool {
+        match marks[id.0] {
+            Mark::Visited => return false,
+            Mark::Visiting => {
+                path.push(id);
+                return true;
+            }
+            Mark::Unvisited => {}
+        }
+
+        marks[id.0] = Mark::Visiting;
+        path.push(id);
+
+        for dependency in &updates[id.0].info.depends {
+            if visit(updates, *dependency, marks, path) {
+                return true;
+            }
+        }
+
+        path.pop();
+        marks[id.0] = Mark::Visited;
+        false
+    }
+
+    let mut marks = vec![Mark::Unvisited; updates.len()];
+    for update in updates {
+        let mut path = Vec::new();
+        if visit(updates, update.id, &mut marks, &mut path) {
+            // Trim the path leading up to the cycle
+            let end = *path.last().unwrap();
+            let start = path.iter().position(|id| *id == end).unwrap();
+            return Some(path.split_off(start));
+        }
+    }
+
+    None
+}
+
+/// Make conflicts symmetric, if a conflicts with b then b must also conflict with a.
+fn symmetric_conflicts(updates: &mut [Update]) {
+    let mut missing = Vec::new();
+    for update in updates.iter() {
+        for conflict in &update.info.conflicts {
+            if !updates[conflict.0].info.conflicts.contains(&update.id) {
+                missing.push((*conflict, update.id));
+            }
+        }
+    }
+
+    for (id, conflict) in missing {
+        let conflicts = &mut updates[id.0].info.conflicts;
+        if !conflicts.contains(&conflict) {
+            conflicts.push(conflict);
+        }
+    }
+}
+
+/// Calculate the length of the longest chain of dependents starting at each update, weighting
+/// each update by its estimated duration. The dependency graph must not contain cycles.
+fn critical_paths(updates: &[Arc<Update>], weights: &[Duration]) -> Vec<Duration> {
+    let mut dependents = vec![Vec::new(); updates.len()];
+    for update in updates {
+        for dependency in &update.info.depends {
+            dependents[dependency.0].push(update.id);
+        }
+    }
+
+    fn visit(
+        id: UpdateId,
+        dependents: &[Vec<UpdateId>],
+        weights: &[Duration],
+        paths: &mut [Option<Duration>],
+    ) -> Duration {
+        if let Some(path) = paths[id.0] {
+            return path;
+        }
+
+        let longest_dependent = dependents[id.0]
+            .iter()
+            .map(|dependent| visit(*dependent, dependents, weights, paths))
+            .max()
+            .unwrap_or(Duration::ZERO);
+
+        let path = weights[id.0] + longest_dependent;
+        paths[id.0] = Some(path);
+        path
+    }
+
+    let mut paths = vec![None; updates.len()];
+    for update in updates {
+        visit(update.id, &dependents, weights, &mut paths);
+    }
+
+    paths.into_iter().map(Option::unwrap_or_default).collect()
+}
+
 impl Updater {
-    pub fn new(updates: Vec<Update>) -> Result<Updater> {
+    pub fn new(mut updates: Vec<Update>) -> Result<Updater> {
         validate_updates(&updates)?;
+        symmetric_conflicts(&mut updates);
+
+        let events = Arc::new(EventBus::default());
+        let updates: Vec<_> = updates
+            .into_iter()
+            .map(|mut update| {
+                update.events = events.clone();
+                Arc::new(update)
+            })
+            .collect();
+        let critical_path = critical_paths(&updates, &vec![DEFAULT_DURATION; updates.len()]);
+
+        let (tx, rx) = channel();
+        let global_state = Arc::new(GlobalState {
+            should_try_scheduling: tx,
+            has_stdin_lock: Mutex::new(StdinLock::default()),
+            events,
+        });
+
         Ok(Updater {
-            updates: updates.into_iter().map(Arc::new).collect(),
+            updates,
+            critical_path,
+            global_state,
+            notifications: rx,
+            hooks: Hooks::default(),
         })
     }
 
-    pub fn updates(&self) -> &[Arc<Update>] {
-        &self.updates
+    /// Get a handle that can cancel the run
+    pub fn cancel_handle(&self) -> CancelHandle {
+        CancelHandle {
+            should_try_scheduling: self.global_state.should_try_scheduling.clone(),
+        }
     }
 
-    pub fn all_done(&self) -> bool {
-        self.updates
-            .iter()
-            .all(|update| update.state.get().is_done())
+    /// Run commands when input is needed, an update is done and the run is done
+    pub fn hooks(mut self, hooks: Hooks) -> Self {
+        self.hooks = hooks;
+
+        self
     }
 
-    pub fn done(&self) -> HashSet<UpdateId> {
-        self.updates
-            .iter()
-            .filter(|update| update.state.get().is_done())
-            .map(|update| update.id)
-            .collect()
+    /// Receive an [Event] for everything that happens from now on
+    pub fn subscribe(&self) -> Receiver<Event> {
+        self.global_state.events.subscribe()
     }
 
-    pub fn successful(&self) -> HashSet<UpdateId> {
-        self.updates
+    /// Use the durations of previous runs to prefer updates on the critical path of the
+    /// dependency graph, updates without history are estimated using the mean duration.
+    pub fn history(mut self, history: &History) -> Self {
+        let default = history.mean().unwrap_or(DEFAULT_DURATION);
+        let weights: Vec<_> = self
+            .updates
             .iter()
-            .filter(|update| update.state.get() == State::Success)
-            .map(|update| update.id)
-            .collect()
+            .map(|update| history.get(&update.name).unwrap_or(default))
+            .collect();
+
+        self.critical_path = critical_paths(&self.updates, &weights);
+
+        self
     }
 
-    pub fn running(&self) -> HashSet<UpdateId> {
-        self.updates
-            .iter()
-            .filter(|update| update.state.get().is_running())
-            .map(|update| update.id)
-            .collect()
+    /// Write the output of each update to its own file in the run's log directory
+    pub fn logs(self, logs: &RunLogs) -> Self {
+        for update in &self.updates {
+            *update.log_path.lock().unwrap() = Some(logs.path(&update.name));
+        }
+
+        self
     }
 
-    pub fn running_count(&self) -> usize {
-        self.updates
-            .iter()
-            .filter(|update| update.state.get().is_running())
-            .count()
+    pub fn updates(&self) -> &[Arc<Update>] {
+        &self.updates
+    }
+
+    /// Order in which to start updates: highest priority first, ties are broken by the longest
+    /// critical path and then by the lowest ID (which is name order for updates created with
+    /// [Updater::try_from_config]).
+    fn schedule_order(&self, update: &Update) -> ScheduleKey {
+        (
+            update.info.priority,
+            self.critical_path[update.id.0],
+            Reverse(update.id.0),
+        )
     }
 
-    fn greedy_select_update(&self, global_state: &GlobalState) -> Option<UpdateId> {
-        let stdin_in_use = global_state.has_stdin_lock.lock().unwrap().is_some();
+    /// Simulate a run without starting anything, assuming every update takes the same time.
+    ///
+    /// Updates that would fail without an attempt (see [Update::expected_state]) are still
+    /// planned, but everything depending on them is ignored. Updates that could never be
+    /// started are left out.
+    pub fn plan(&self, threads: usize) -> Plan {
+        let mut order: Vec<_> = self.updates.iter().collect();
+        order.sort_by_key(|update| Reverse(self.schedule_order(update)));
+
+        let mut not_satisfied: HashSet<_> = self
+            .updates
+            .iter()
+            .filter(|update| {
+                update
+                    .expected_state()
+                    .is_some_and(|state| !state.satisfies_dependents())
+            })
+            .map(|update| update.id)
+            .collect();
+        let mut ignored = Vec::new();
+        // Dependencies can't have cycles so this ends
+        loop {
+            let newly_ignored: Vec<_> = self
+                .updates
+                .iter()
+                .filter(|update| {
+                    !not_satisfied.contains(&update.id)
+                        && update
+                            .info
+                            .depends
+                            .iter()
+                            .any(|id| not_satisfied.contains(id))
+                })
+                .map(|update| update.id)
+                .collect();
+            if newly_ignored.is_empty() {
+                break;
+            }
+            not_satisfied.extend(newly_ignored.iter().copied());
+            ignored.extend(newly_ignored);
+        }
+        ignored.sort_by_key(|id| id.0);
+
+        let mut planned = HashSet::new();
+        let mut waves = Vec::new();
 
-        let done = self.done();
-        let successful = self.successful();
-        let running = self.running();
+        loop {
+            let mut wave: Vec<UpdateId> = Vec::new();
+            let mut uses_input = false;
 
-        let mut valid_pending = self.updates.iter().filter(|update| {
-            // Set ignored if cannot run
-            for dependecy in update.info.depends.iter() {
-                let is_done = done.contains(dependecy);
-                let is_success = successful.contains(dependecy);
-                if is_done && !is_success {
-                    update.state.set(State::Ignored);
-                    return false;
+            for update in order.iter() {
+                if wave.len() >= threads {
+                    break;
                 }
+
+                if planned.contains(&update.id)
+                    || ignored.contains(&update.id)
+                    || (uses_input && update.info.input)
+                    || !update.info.depends.iter().all(|id| planned.contains(id))
+                    || update.info.conflicts.iter().any(|id| wave.contains(id))
+                {
+                    continue;
+                }
+
+                uses_input |= update.info.input;
+                wave.push(update.id);
             }
 
-            update.state.get() == State::Pending
-                && (!stdin_in_use || !update.info.input)
-                && update.info.depends.iter().all(|id| successful.contains(id))
-                && update.info.conflicts.iter().all(|id| !running.contains(id))
-        });
+            if wave.is_empty() {
+                break;
+            }
+
+            planned.extend(wave.iter().copied());
+            waves.push(wave);
+        }
 
-        valid_pending.next().map(|update| update.id)
+        Plan { waves, ignored }
     }
 
     pub fn run(self, threads: usize) -> Vec<Arc<Update>> {
-        let (tx, rx) = channel();
+        let global_state = &self.global_state;
+        let mut cancelled = false;
 
-        let global_state = Arc::new(GlobalState {
-            should_try_scheduling: tx.clone(),
-            has_stdin_lock: Mutex::new(None),
+        let hooks = (!self.hooks.is_empty()).then(|| {
+            self.hooks
+                .clone()
+                .start(self.updates.clone(), global_state.events.subscribe())
         });
 
-        while !self.all_done() {
-            for _ in self.running_count()..threads {
-                let Some(next) = self.greedy_select_update(&global_state) else {
-                    break;
-                };
+        let keys = self
+            .updates
+            .iter()
+            .map(|update| self.schedule_order(update))
+            .collect();
+        let mut scheduler = Scheduler::new(&self.updates, keys);
+
+        loop {
+            let next_retry = scheduler.release_retries(Instant::now());
 
-                let update = Arc::clone(&self.updates[next.0]);
-                let global_state = Arc::clone(&global_state);
-                let tx = tx.clone();
+            if !cancelled {
+                while scheduler.running() < threads {
+                    let Some(next) = scheduler.next(global_state.stdin_holder().is_some()) else {
+                        break;
+                    };
 
-                std::thread::spawn(move || {
-                    (update.run)(&update, &global_state);
+                    let update = Arc::clone(&self.updates[next.0]);
+                    let global_state = Arc::clone(global_state);
 
-                    // Cleanup un-closed stdin locks
-                    let mut stdin_lock = global_state.has_stdin_lock.lock().unwrap();
-                    if *stdin_lock == Some(update.id) {
-                        *stdin_lock = None;
+                    scheduler.started(next);
+                    if update.info.input {
+                        global_state.reserve_stdin(update.id);
                     }
+                    std::thread::spawn(move || {
+                        // A panic (in the checks or the runner) must still leave the update in a
+                        // final state
+                        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                            // Leave pending before checking whether the update can run, which
+                            // may take a while (e.g. running a probe)
+                            update.set_state(State::Starting);
+
+                            // Updates that can't run finish without an attempt
+                            if update.check_runnable() {
+                                update.attempts.fetch_add(1, Ordering::SeqCst);
+                                (update.run)(&update, &global_state)
+                            }
+                        }));
+                        if let Err(panic) = result {
+                            // Don't leave the update's process running
+                            update.signal(libc::SIGKILL);
+                            update.untrack_child();
+                            update.set_state(State::Panicked(panic_message(panic.as_ref())));
+                        }
+
+                        // Cleanup un-closed stdin locks
+                        global_state.release_stdin(update.id);
+
+                        // Notify that we finished
+                        global_state
+                            .should_try_scheduling
+                            .send(Notification::Finished(update.id))
+                    });
+
+                    // A pending update could still be cancelled
+                    self.updates[next.0].state.wait_until_not(State::Pending);
+                }
+            }
+
+            if scheduler.all_done() {
+                break;
+            }
 
-                    // Notify that we finished
-                    tx.send(update.id)
-                });
+            // Nothing is running (or about to release input) so nothing will ever change
+            if !cancelled
+                && scheduler.running() == 0
+                && global_state.stdin_holder().is_none()
+                && !scheduler.has_retries()
+            {
+                scheduler.mark_unschedulable();
+                break;
+            }
 
-                self.updates[next.0].state.wait_until_not(State::Pending);
+            let notification = match next_retry {
+                Some(next_retry) if !cancelled => self
+                    .notifications
+                    .recv_timeout(next_retry.saturating_duration_since(Instant::now()))
+                    .ok(),
+                _ => Some(self.notifications.recv().unwrap()),
+            };
+
+            for notification in notification
+                .into_iter()
+                .chain(self.notifications.try_iter())
+            {
+                match notification {
+                    Notification::Finished(id) => scheduler.finished(id),
+                    Notification::InputReleased(_) => {}
+                    Notification::Cancel(signal) => {
+                        if !cancelled {
+                            cancelled = true;
+                            scheduler.cancel();
+                        }
+                        for update in self.updates.iter() {
+                            update.cancel(signal);
+                        }
+                    }
+                }
             }
+        }
+
+        global_state.events.emit(EventKind::RunFinished);
 
-            rx.recv().unwrap();
-            while rx.try_recv().is_ok() {}
+        // Wait for hooks that are still running so that they (and the run done hook) finish before
+        // we return
+        if let Some(hooks) = hooks {
+            let _ = hooks.join();
         }
 
         self.updates
@@ -174,8 +527,11 @@ impl Updater {
     pub fn try_from_config(config: Config) -> Result<(UpdaterConfig, Updater)> {
         let mut id_map = HashMap::new();
 
-        let update_configs: Vec<_> = config
-            .updates
+        // Sort by name so that IDs (and therefore scheduling order) are stable between runs
+        let mut update_configs: Vec<_> = config.updates.into_iter().collect();
+        update_configs.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let update_configs: Vec<_> = update_configs
             .into_iter()
             .enumerate()
             .map(|(i, (name, config))| {
@@ -193,3 +549,160 @@ impl Updater {
         Ok((config.updater, Updater::new(updates)?))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cycle(depends: &[&[usize]]) -> Option<Vec<usize>> {
+        let updates: Vec<_> = depends
+            .iter()
+            .enumerate()
+            .map(|(id, depends)| Update::for_test(id, depends, &[]))
+            .collect();
+
+        find_dependency_cycle(&updates).map(|path| path.into_iter().map(|id| id.0).collect())
+    }
+
+    #[test]
+    fn no_cycle() {
+        assert_eq!(cycle(&[&[], &[0], &[0, 1]]), None);
+    }
+
+    #[test]
+    fn self_dependency() {
+        assert_eq!(cycle(&[&[0]]), Some(vec![0, 0]));
+    }
+
+    #[test]
+    fn cycle_path_excludes_the_path_leading_to_it() {
+        // 0 -> 1 -> 2 -> 3 -> 1
+        assert_eq!(cycle(&[&[1], &[2], &[3], &[1]]), Some(vec![1, 2, 3, 1]));
+    }
+
+    #[test]
+    fn conflicts_are_made_symmetric() {
+        let mut updates = vec![
+            Update::for_test(0, &[], &[1]),
+            Update::for_test(1, &[], &[]),
+        ];
+        symmetric_conflicts(&mut updates);
+
+        assert_eq!(updates[1].info.conflicts, vec![UpdateId(0)]);
+        assert_eq!(updates[0].info.conflicts, vec![UpdateId(1)]);
+    }
+
+    fn secs(secs: &[u64]) -> Vec<Duration> {
+        secs.iter().copied().map(Duration::from_secs).collect()
+    }
+
+    #[test]
+    fn critical_path_is_the_longest_chain_of_dependents() {
+        // 1 and 2 depend on 0, 3 depends on 1
+        let updates: Vec<_> = [&[][..], &[0], &[0], &[1]]
+            .iter()
+            .enumerate()
+            .map(|(id, depends)| Arc::new(Update::for_test(id, depends, &[])))
+            .collect();
+
+        let paths = critical_paths(&updates, &secs(&[1, 2, 4, 3]));
+
+        assert_eq!(paths, secs(&[6, 5, 4, 3]));
+    }
+
+    #[test]
+    fn updates_without_history_are_weighted_by_the_mean() {
+        // 1 depends on 0
+        let updater = Updater::new(vec![
+            Update::for_test(0, &[], &[]),
+            Update::for_test(1, &[0], &[]),
+            Update::for_test(2, &[], &[]),
+        ])
+        .unwrap();
+        assert_eq!(updater.critical_path, secs(&[2, 1, 1]));
+
+        let mut history = History::new();
+        history.record("update0", Duration::from_secs(2));
+        history.record("update2", Duration::from_secs(6));
+        let updater = updater.history(&history);
+
+        // update1 has no history so is weighted by the mean of 4s
+        assert_eq!(updater.critical_path, secs(&[6, 4, 6]));
+    }
+
+    fn waves(plan: &Plan) -> Vec<Vec<usize>> {
+        plan.waves
+            .iter()
+            .map(|wave| wave.iter().map(|id| id.0).collect())
+            .collect()
+    }
+
+    #[test]
+    fn plan_limits_waves_to_the_threads() {
+        let updater = Updater::new((0..5).map(|id| Update::for_test(id, &[], &[])).collect());
+
+        assert_eq!(
+            waves(&updater.unwrap().plan(2)),
+            [vec![0, 1], vec![2, 3], vec![4]]
+        );
+    }
+
+    #[test]
+    fn plan_uses_input_once_per_wave() {
+        let updates = (0..3)
+            .map(|id| {
+                let mut update = Update::for_test(id, &[], &[]);
+                update.info.input = id != 1;
+                update
+            })
+            .collect();
+        let updater = Updater::new(updates).unwrap();
+
+        assert_eq!(waves(&updater.plan(4)), [vec![0, 1], vec![2]]);
+    }
+
+    #[test]
+    fn plan_keeps_conflicts_apart() {
+        let updater = Updater::new(vec![
+            Update::for_test(0, &[], &[1]),
+            Update::for_test(1, &[], &[]),
+            Update::for_test(2, &[], &[]),
+        ])
+        .unwrap();
+
+        assert_eq!(waves(&updater.plan(4)), [vec![0, 2], vec![1]]);
+    }
+
+    #[test]
+    fn plan_starts_updates_after_their_dependencies() {
+        // 0 depends on 1 which depends on 2
+        let updater = Updater::new(vec![
+            Update::for_test(0, &[1], &[]),
+            Update::for_test(1, &[2], &[]),
+            Update::for_test(2, &[], &[]),
+            Update::for_test(3, &[], &[]),
+        ])
+        .unwrap();
+
+        assert_eq!(waves(&updater.plan(4)), [vec![2, 3], vec![1], vec![0]]);
+    }
+
+    #[test]
+    fn plan_ignores_dependents_of_failing_updates() {
+        let mut missing = Update::for_test(0, &[], &[]);
+        missing.program =
+            parallel_update_config::types::Program::new("parallel-update-missing-executable");
+        let updater = Updater::new(vec![
+            missing,
+            Update::for_test(1, &[0], &[]),
+            Update::for_test(2, &[1], &[]),
+            Update::for_test(3, &[], &[]),
+        ])
+        .unwrap();
+
+        let plan = updater.plan(4);
+
+        assert_eq!(waves(&plan), [vec![0, 3]]);
+        assert_eq!(plan.ignored, [UpdateId(1), UpdateId(2)]);
+    }
+}